@@ -0,0 +1,292 @@
+//! This crate provides `#[derive(PrettyPrint)]` for the `PrettyPrint` trait of
+//! [`miden-formatting`](https://docs.rs/miden-formatting).
+//!
+//! Rather than depending on this crate directly, enable the `derive` feature of
+//! `miden-formatting`, which re-exports the macro as `miden_formatting::prettier::PrettyPrint`.
+//!
+//! # Layout
+//!
+//! * Structs with named fields, and enum variants with named fields, are rendered like
+//!   `Name { a: 1, b: 2 }`, see `prettier::record`
+//! * Tuple structs and tuple variants are rendered like `Name(1, 2)`, see `prettier::tuple`
+//! * Unit structs and unit variants are rendered as their name
+//!
+//! Enum variants are rendered by their name alone, e.g. `Some(1)`, not `Option::Some(1)`.
+//!
+//! # Attributes
+//!
+//! The following attributes are supported on fields:
+//!
+//! * `#[pretty(skip)]` omits the field from the output
+//! * `#[pretty(rename = "name")]` renders a named field using the given name
+//! * `#[pretty(flatten)]` always renders the value of the field on a single line
+//!
+//! Every type parameter of the type must implement `PrettyPrint`.
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    ext::IdentExt, parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error,
+    Fields, Ident, LitStr, Result,
+};
+
+/// Derive an implementation of `PrettyPrint` which renders each field of a struct or enum.
+///
+/// See the [crate] documentation for the layout and supported attributes.
+#[proc_macro_derive(PrettyPrint, attributes(pretty))]
+pub fn derive_pretty_print(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// The path to the `prettier` module of `miden-formatting`.
+///
+/// The generated code only uses the public API of this module.
+fn prettier() -> TokenStream {
+    quote!(::miden_formatting::prettier)
+}
+
+fn expand(mut input: DeriveInput) -> Result<TokenStream> {
+    no_pretty_attrs(&input.attrs, "types")?;
+
+    let prettier = prettier();
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, doc) = render_fields(&name.unraw().to_string(), &data.fields)?;
+            quote! {
+                let #name #pattern = self;
+                #doc
+            }
+        },
+        Data::Enum(data) => {
+            let arms = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    no_pretty_attrs(&variant.attrs, "variants")?;
+                    let ident = &variant.ident;
+                    let (pattern, doc) =
+                        render_fields(&ident.unraw().to_string(), &variant.fields)?;
+                    Ok(quote!(Self::#ident #pattern => #doc,))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if arms.is_empty() {
+                // An enum without variants has no values to render
+                quote!(match *self {})
+            } else {
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        },
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "#[derive(PrettyPrint)] does not support unions",
+            ));
+        },
+    };
+
+    let type_params = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    if !type_params.is_empty() {
+        let where_clause = input.generics.make_where_clause();
+        for param in type_params {
+            where_clause.predicates.push(syn::parse_quote!(#param: #prettier::PrettyPrint));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics #prettier::PrettyPrint for #name #ty_generics #where_clause {
+            fn render(&self) -> #prettier::Document {
+                #body
+            }
+        }
+    })
+}
+
+/// The attributes of a single field, see the [crate] documentation
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    rename: Option<LitStr>,
+    flatten: bool,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("pretty")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    if parsed.skip {
+                        return Err(meta.error("duplicate `skip` attribute"));
+                    }
+                    parsed.skip = true;
+                } else if meta.path.is_ident("rename") {
+                    if parsed.rename.is_some() {
+                        return Err(meta.error("duplicate `rename` attribute"));
+                    }
+                    parsed.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("flatten") {
+                    if parsed.flatten {
+                        return Err(meta.error("duplicate `flatten` attribute"));
+                    }
+                    parsed.flatten = true;
+                } else {
+                    return Err(meta.error(
+                        "unknown attribute, expected one of `skip`, `rename` or `flatten`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+        if parsed.skip && (parsed.rename.is_some() || parsed.flatten) {
+            let attr = attrs.iter().find(|attr| attr.path().is_ident("pretty")).unwrap();
+            return Err(Error::new(
+                attr.span(),
+                "`skip` cannot be combined with other attributes, as the field is not rendered",
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+/// Returns an error if any of `attrs` is a `#[pretty]` attribute, which are only valid on fields
+fn no_pretty_attrs(attrs: &[Attribute], what: &str) -> Result<()> {
+    match attrs.iter().find(|attr| attr.path().is_ident("pretty")) {
+        Some(attr) => Err(Error::new(
+            attr.span(),
+            format!("#[pretty] attributes are only supported on fields, not on {what}"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Generate the pattern which binds `fields`, and an expression which renders them as `name`
+fn render_fields(name: &str, fields: &Fields) -> Result<(TokenStream, TokenStream)> {
+    let prettier = prettier();
+    let render = |binding: &Ident, attrs: &FieldAttrs| {
+        let doc = quote!(#prettier::PrettyPrint::render(#binding));
+        if attrs.flatten {
+            quote!(#prettier::flatten(#doc))
+        } else {
+            doc
+        }
+    };
+    match fields {
+        Fields::Named(fields) => {
+            let mut bindings = vec![];
+            let mut docs = vec![];
+            for field in fields.named.iter() {
+                let ident = field.ident.as_ref().unwrap();
+                let attrs = FieldAttrs::parse(&field.attrs)?;
+                if attrs.skip {
+                    bindings.push(quote!(#ident: _));
+                    continue;
+                }
+                let binding = format_ident!("__{}", ident);
+                let label = match attrs.rename.as_ref() {
+                    Some(rename) => rename.value(),
+                    None => ident.unraw().to_string(),
+                };
+                let doc = render(&binding, &attrs);
+                bindings.push(quote!(#ident: #binding));
+                docs.push(quote!((#label, #doc)));
+            }
+            Ok((quote!({ #(#bindings,)* }), quote!(#prettier::record(#name, &[#(#docs),*]))))
+        },
+        Fields::Unnamed(fields) => {
+            let mut bindings = vec![];
+            let mut docs = vec![];
+            for (index, field) in fields.unnamed.iter().enumerate() {
+                let attrs = FieldAttrs::parse(&field.attrs)?;
+                if let Some(rename) = attrs.rename.as_ref() {
+                    return Err(Error::new(
+                        rename.span(),
+                        "`rename` is only supported on named fields",
+                    ));
+                }
+                if attrs.skip {
+                    bindings.push(quote!(_));
+                    continue;
+                }
+                let binding = Ident::new(&format!("__{index}"), Span::call_site());
+                docs.push(render(&binding, &attrs));
+                bindings.push(quote!(#binding));
+            }
+            Ok((quote!((#(#bindings),*)), quote!(#prettier::tuple(#name, &[#(#docs),*]))))
+        },
+        Fields::Unit => Ok((quote!(), quote!(#prettier::const_text(#name)))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the error produced when expanding the derive for `input`
+    fn expand_err(input: &str) -> String {
+        let input = syn::parse_str::<DeriveInput>(input).unwrap();
+        match expand(input) {
+            Ok(tokens) => panic!("expected an error, but the derive expanded to: {tokens}"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn attribute_misuse() {
+        assert_eq!(
+            expand_err("struct Foo { #[pretty(hidden)] a: u8 }"),
+            "unknown attribute, expected one of `skip`, `rename` or `flatten`"
+        );
+        assert_eq!(
+            expand_err("struct Foo { #[pretty(skip, skip)] a: u8 }"),
+            "duplicate `skip` attribute"
+        );
+        assert_eq!(expand_err("struct Foo { #[pretty(rename)] a: u8 }"), "expected `=`");
+        assert_eq!(
+            expand_err("struct Foo { #[pretty(rename = b)] a: u8 }"),
+            "expected string literal"
+        );
+        assert_eq!(
+            expand_err("struct Foo { #[pretty(skip)] #[pretty(flatten)] a: u8 }"),
+            "`skip` cannot be combined with other attributes, as the field is not rendered"
+        );
+        assert_eq!(
+            expand_err("struct Foo(#[pretty(rename = \"a\")] u8);"),
+            "`rename` is only supported on named fields"
+        );
+        assert_eq!(
+            expand_err("#[pretty(skip)] struct Foo;"),
+            "#[pretty] attributes are only supported on fields, not on types"
+        );
+        assert_eq!(
+            expand_err("enum Foo { #[pretty(rename = \"b\")] A }"),
+            "#[pretty] attributes are only supported on fields, not on variants"
+        );
+        assert_eq!(
+            expand_err("union Foo { a: u8 }"),
+            "#[derive(PrettyPrint)] does not support unions"
+        );
+    }
+
+    #[test]
+    fn type_parameters_are_bounded() {
+        let input = syn::parse_str::<DeriveInput>("struct Foo<'a, T: Copy>(&'a T);").unwrap();
+        let output = expand(input).unwrap().to_string();
+        assert!(
+            output.contains(
+                "where T : :: miden_formatting :: prettier :: PrettyPrint { fn render (& self)"
+            ),
+            "unexpected expansion: {output}"
+        );
+    }
+}
@@ -1,6 +1,6 @@
 //! This module provides various utilties for formatting values as hexadecimal bytes.
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use core::fmt;
 
 /// This trait represents a value that can be converted to a string of hexadecimal digits which
@@ -18,6 +18,117 @@ pub trait ToHex {
     fn to_hex(&self) -> String;
     /// Same as [ToHex::to_hex], but ensures the output contains a leading `0x` prefix.
     fn to_hex_with_prefix(&self) -> String;
+    /// Same as [ToHex::to_hex], but uses uppercase hexadecimal digits.
+    fn to_hex_upper(&self) -> String {
+        self.to_hex().to_ascii_uppercase()
+    }
+    /// Same as [ToHex::to_hex_upper], but ensures the output contains a leading `0x` prefix.
+    ///
+    /// Only the digits are uppercase, the prefix is `0x`. Use
+    /// [ToHex::to_hex_upper_with_upper_prefix] if the prefix should be uppercase too.
+    fn to_hex_upper_with_prefix(&self) -> String {
+        format!("0x{}", self.to_hex_upper())
+    }
+    /// Same as [ToHex::to_hex_upper], but ensures the output contains a leading `0X` prefix.
+    fn to_hex_upper_with_upper_prefix(&self) -> String {
+        format!("0X{}", self.to_hex_upper())
+    }
+}
+
+/// This trait represents a value that can be decoded from a string of hexadecimal digits, such as
+/// those produced by [ToHex].
+///
+/// Decoding is tolerant of the formatting of the input: it may have a leading `0x` or `0X`
+/// prefix, and digits may be uppercase, lowercase, or a mix of both. Every byte must be encoded
+/// using exactly two digits.
+pub trait FromHex: Sized {
+    /// The error returned when `hex` cannot be decoded, e.g. [FromHexError]
+    type Error;
+
+    /// Decode a value from the hexadecimal digits in `hex`
+    fn from_hex(hex: &str) -> Result<Self, Self::Error>;
+}
+
+/// The error returned when decoding a string of hexadecimal digits fails, see [FromHex]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FromHexError {
+    /// The input contains a character, `found`, which is not a hexadecimal digit, at byte offset
+    /// `offset` of the input, including any `0x` prefix
+    InvalidDigit { offset: usize, found: char },
+    /// The input contains an odd number of digits, `len`, not counting any `0x` prefix, so the
+    /// last digit is unpaired
+    OddLength { len: usize },
+    /// The input encodes `actual` bytes, but a value of `expected` bytes was required
+    InvalidLength { expected: usize, actual: usize },
+    /// The destination buffer is too small to hold the decoded bytes, see [decode_to_slice]
+    BufferTooSmall(BufferTooSmall),
+}
+
+/// An alias for [FromHexError]
+pub type HexError = FromHexError;
+
+impl fmt::Display for FromHexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidDigit { offset, found } => {
+                write!(f, "invalid hexadecimal digit {found:?} at offset {offset}")
+            },
+            Self::OddLength { len } => write!(f, "odd number of hexadecimal digits: {len}"),
+            Self::InvalidLength { expected, actual } => {
+                write!(f, "expected {expected} bytes of hexadecimal digits, got {actual}")
+            },
+            Self::BufferTooSmall(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for FromHexError {}
+
+#[cfg(all(feature = "std", not(feature = "core-error")))]
+impl std::error::Error for FromHexError {}
+
+impl FromHex for Vec<u8> {
+    type Error = FromHexError;
+
+    fn from_hex(hex: &str) -> Result<Self, Self::Error> {
+        Ok(hex_digits(hex)?.chunks_exact(2).map(decode_pair).collect())
+    }
+}
+
+/// Strip the optional prefix from `hex`, and validate that the remainder consists of an even
+/// number of hexadecimal digits, returning them as ASCII bytes
+fn hex_digits(hex: &str) -> Result<&[u8], FromHexError> {
+    let (prefix_len, digits) = match hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X")) {
+        Some(digits) => (2, digits),
+        None => (0, hex),
+    };
+    if let Some((index, found)) = digits.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(FromHexError::InvalidDigit { offset: prefix_len + index, found });
+    }
+    if digits.len() % 2 != 0 {
+        return Err(FromHexError::OddLength { len: digits.len() });
+    }
+    // All of the digits are ASCII, so each is a single byte
+    Ok(digits.as_bytes())
+}
+
+/// Decode a pair of validated ASCII hexadecimal digits into the byte they represent
+fn decode_pair(pair: &[u8]) -> u8 {
+    let value = |digit: u8| (digit as char).to_digit(16).unwrap() as u8;
+    (value(pair[0]) << 4) | value(pair[1])
+}
+
+impl<const N: usize> FromHex for [u8; N] {
+    type Error = FromHexError;
+
+    fn from_hex(hex: &str) -> Result<Self, Self::Error> {
+        let bytes = Vec::<u8>::from_hex(hex)?;
+        let actual = bytes.len();
+        bytes
+            .try_into()
+            .map_err(|_| FromHexError::InvalidLength { expected: N, actual })
+    }
 }
 
 impl ToHex for [u8] {
@@ -28,6 +139,53 @@ impl ToHex for [u8] {
     fn to_hex_with_prefix(&self) -> String {
         format!("{:#x}", DisplayHex(self))
     }
+
+    fn to_hex_upper(&self) -> String {
+        format!("{:X}", DisplayHex(self))
+    }
+
+    fn to_hex_upper_with_prefix(&self) -> String {
+        format!("{:#X}", DisplayHex(self))
+    }
+}
+
+impl<const N: usize> ToHex for [u8; N] {
+    #[inline]
+    fn to_hex(&self) -> String {
+        self.as_slice().to_hex()
+    }
+
+    #[inline]
+    fn to_hex_with_prefix(&self) -> String {
+        self.as_slice().to_hex_with_prefix()
+    }
+}
+
+impl<T: ?Sized + ToHex> ToHex for &T {
+    #[inline]
+    fn to_hex(&self) -> String {
+        (**self).to_hex()
+    }
+
+    #[inline]
+    fn to_hex_with_prefix(&self) -> String {
+        (**self).to_hex_with_prefix()
+    }
+
+    #[inline]
+    fn to_hex_upper(&self) -> String {
+        (**self).to_hex_upper()
+    }
+
+    #[inline]
+    fn to_hex_upper_with_prefix(&self) -> String {
+        (**self).to_hex_upper_with_prefix()
+    }
+
+    #[inline]
+    fn to_hex_upper_with_upper_prefix(&self) -> String {
+        (**self).to_hex_upper_with_upper_prefix()
+    }
 }
 
 impl<'a> ToHex for DisplayHex<'a> {
@@ -38,6 +196,14 @@ impl<'a> ToHex for DisplayHex<'a> {
     fn to_hex_with_prefix(&self) -> String {
         format!("{:#x}", self)
     }
+
+    fn to_hex_upper(&self) -> String {
+        format!("{:X}", self)
+    }
+
+    fn to_hex_upper_with_prefix(&self) -> String {
+        format!("{:#X}", self)
+    }
 }
 
 /// Construct a [String] containing the hexadecimal representation of `bytes`
@@ -46,6 +212,144 @@ pub fn to_hex(bytes: impl AsRef<[u8]>) -> String {
     bytes.as_ref().to_hex()
 }
 
+/// Decode the bytes represented by the hexadecimal digits in `hex`, see [FromHex]
+#[inline]
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, FromHexError> {
+    Vec::from_hex(hex)
+}
+
+/// The lowercase hexadecimal digits, indexed by value
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// The error returned by [encode_to_slice] and [decode_to_slice] when the destination buffer is
+/// too small to hold the result
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BufferTooSmall {
+    /// The number of bytes needed to hold the result
+    pub required: usize,
+    /// The size of the destination buffer
+    pub actual: usize,
+}
+
+impl fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "buffer too small: {} bytes are required, but only {} are available",
+            self.required, self.actual
+        )
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for BufferTooSmall {}
+
+#[cfg(all(feature = "std", not(feature = "core-error")))]
+impl std::error::Error for BufferTooSmall {}
+
+/// Encode `src` as lowercase hexadecimal digits into `dst`, returning the number of bytes written.
+///
+/// Exactly `2 * src.len()` bytes are written to the front of `dst`, the remainder of `dst` is left
+/// untouched. If `dst` is too small, nothing is written and an error is returned. The resulting
+/// digits do _not_ have a leading `0x` prefix.
+///
+/// Unlike [to_hex], this does not allocate.
+pub fn encode_to_slice(src: &[u8], dst: &mut [u8]) -> Result<usize, BufferTooSmall> {
+    let required = src.len() * 2;
+    if dst.len() < required {
+        return Err(BufferTooSmall { required, actual: dst.len() });
+    }
+    for (byte, pair) in src.iter().zip(dst.chunks_exact_mut(2)) {
+        pair[0] = DIGITS[(byte >> 4) as usize];
+        pair[1] = DIGITS[(byte & 0xf) as usize];
+    }
+    Ok(required)
+}
+
+/// Decode the hexadecimal digits in `src` into `dst`, returning the number of bytes written.
+///
+/// The input is accepted in the same formats as [FromHex]. Exactly `digits / 2` bytes are written
+/// to the front of `dst`, the remainder of `dst` is left untouched. If the input is invalid, or
+/// `dst` is too small, nothing is written and an error is returned.
+///
+/// Unlike [from_hex], this does not allocate.
+pub fn decode_to_slice(src: &str, dst: &mut [u8]) -> Result<usize, FromHexError> {
+    let digits = hex_digits(src)?;
+    let required = digits.len() / 2;
+    if dst.len() < required {
+        return Err(FromHexError::BufferTooSmall(BufferTooSmall { required, actual: dst.len() }));
+    }
+    for (pair, byte) in digits.chunks_exact(2).zip(dst.iter_mut()) {
+        *byte = decode_pair(pair);
+    }
+    Ok(required)
+}
+
+/// Lazily encode `bytes` as a sequence of lowercase hexadecimal digits, two per byte.
+///
+/// Unlike [to_hex], this does not allocate, which makes it suitable for streaming large inputs
+/// to some other sink. The resulting digits do _not_ have a leading `0x` prefix.
+pub fn encode_iter(bytes: impl IntoIterator<Item = u8>) -> impl Iterator<Item = char> {
+    bytes.into_iter().flat_map(|byte| {
+        [DIGITS[(byte >> 4) as usize] as char, DIGITS[(byte & 0xf) as usize] as char]
+    })
+}
+
+/// An adapter which encodes everything written to it as lowercase hexadecimal digits, two per byte
+/// of the UTF-8 encoding of the written text, and writes the digits to the wrapped writer.
+///
+/// This layers hex encoding over any existing [core::fmt::Display] implementation, without
+/// formatting the value to an intermediate string first. The digits do _not_ have a leading `0x`
+/// prefix.
+///
+/// ```rust
+/// use core::fmt::Write;
+/// use miden_formatting::hex::HexWriter;
+///
+/// let mut writer = HexWriter::new(String::new());
+/// write!(writer, "{}", 42).unwrap();
+/// assert_eq!(writer.into_inner(), "3432");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct HexWriter<W> {
+    inner: W,
+}
+
+impl<W: fmt::Write> HexWriter<W> {
+    /// Wrap `inner`, to which the encoded digits are written
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Get a reference to the wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped writer
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for HexWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // The digits are encoded into a small buffer, to avoid writing them one at a time
+        let mut buf = [0; 64];
+        for chunk in s.as_bytes().chunks(buf.len() / 2) {
+            let len = encode_to_slice(chunk, &mut buf).expect("the buffer fits a whole chunk");
+            let digits = core::str::from_utf8(&buf[..len]).expect("hex digits are ascii");
+            self.inner.write_str(digits)?;
+        }
+        Ok(())
+    }
+}
+
 /// A display helper for formatting a slice of bytes as hex
 /// with different options using Rust's builtin format language
 pub struct DisplayHex<'a>(pub &'a [u8]);
@@ -59,6 +363,36 @@ impl<'a> DisplayHex<'a> {
     {
         Self(item.as_ref())
     }
+
+    /// Left-pad the hexadecimal digits with `0` until there are at least `min_digits` of them.
+    ///
+    /// This is useful when displaying the bytes of a value which must always be shown at a fixed
+    /// width, e.g. 64 digits for a 256-bit value. If there are already `min_digits` digits or more,
+    /// they are displayed unchanged. The `0x` prefix is not counted as digits.
+    #[inline]
+    pub fn with_min_digits(self, min_digits: usize) -> PaddedHex<'a> {
+        PaddedHex { bytes: self.0, min_digits }
+    }
+
+    /// Separate every `group_size` bytes of hexadecimal digits with a separator, e.g. `7f3a 22bc`
+    /// for groups of 2 bytes.
+    ///
+    /// The separator defaults to a space, and can be changed with [GroupedHex::separator]. If the
+    /// number of bytes is not a multiple of `group_size`, the last group is shorter. There is no
+    /// separator after the last group, nor between the `0x` prefix and the first group.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `group_size` is 0.
+    #[inline]
+    pub fn group_bytes(self, group_size: usize) -> GroupedHex<'a> {
+        assert!(group_size != 0, "group size must be non-zero");
+        GroupedHex {
+            bytes: self.0,
+            group_size,
+            separator: ' ',
+        }
+    }
 }
 
 impl<'a> fmt::Display for DisplayHex<'a> {
@@ -70,18 +404,497 @@ impl<'a> fmt::Display for DisplayHex<'a> {
 
 impl<'a> fmt::LowerHex for DisplayHex<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_hex(f, self.0, 0, false)
+    }
+}
+
+impl<'a> fmt::UpperHex for DisplayHex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_hex(f, self.0, 0, true)
+    }
+}
+
+/// Write `bytes` to `f` as hexadecimal digits, left-padded with `0` to at least `min_digits`
+/// digits, and preceded by `0x` if the alternate flag is set
+fn write_hex(f: &mut fmt::Formatter, bytes: &[u8], min_digits: usize, upper: bool) -> fmt::Result {
+    if f.alternate() {
+        f.write_str("0x")?;
+    }
+    for _ in (bytes.len() * 2)..min_digits {
+        f.write_str("0")?;
+    }
+    for byte in bytes.iter() {
+        if upper {
+            write!(f, "{byte:02X}")?;
+        } else {
+            write!(f, "{byte:02x}")?;
+        }
+    }
+    Ok(())
+}
+
+/// A display helper for formatting a slice of bytes as hex, left-padded with `0` to a minimum
+/// number of digits, see [DisplayHex::with_min_digits]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PaddedHex<'a> {
+    bytes: &'a [u8],
+    min_digits: usize,
+}
+
+impl<'a> fmt::Display for PaddedHex<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl<'a> fmt::LowerHex for PaddedHex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_hex(f, self.bytes, self.min_digits, false)
+    }
+}
+
+impl<'a> fmt::UpperHex for PaddedHex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_hex(f, self.bytes, self.min_digits, true)
+    }
+}
+
+impl<'a> ToHex for PaddedHex<'a> {
+    fn to_hex(&self) -> String {
+        format!("{:x}", self)
+    }
+
+    fn to_hex_with_prefix(&self) -> String {
+        format!("{:#x}", self)
+    }
+
+    fn to_hex_upper(&self) -> String {
+        format!("{:X}", self)
+    }
+
+    fn to_hex_upper_with_prefix(&self) -> String {
+        format!("{:#X}", self)
+    }
+}
+
+impl<'a> crate::prettier::PrettyPrint for PaddedHex<'a> {
+    fn render(&self) -> crate::prettier::Document {
+        crate::prettier::text(format!("{:#x}", self))
+    }
+}
+
+/// A display helper for formatting a slice of bytes as hex, with a separator between each group
+/// of bytes, see [DisplayHex::group_bytes]
+///
+/// NOTE: This does not implement [ToHex], as the separators mean its output cannot be decoded by
+/// [FromHex].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GroupedHex<'a> {
+    bytes: &'a [u8],
+    group_size: usize,
+    separator: char,
+}
+
+impl<'a> GroupedHex<'a> {
+    /// Separate each group of bytes with `separator` rather than a space
+    #[inline]
+    pub fn separator(self, separator: char) -> Self {
+        Self { separator, ..self }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, upper: bool) -> fmt::Result {
         if f.alternate() {
             f.write_str("0x")?;
         }
-        for byte in self.0.iter() {
-            write!(f, "{byte:02x}")?;
+        for (i, group) in self.bytes.chunks(self.group_size).enumerate() {
+            if i > 0 {
+                write!(f, "{}", self.separator)?;
+            }
+            if upper {
+                write!(f, "{:X}", DisplayHex(group))?;
+            } else {
+                write!(f, "{:x}", DisplayHex(group))?;
+            }
         }
         Ok(())
     }
 }
 
-impl<'a> crate::prettier::PrettyPrint for DisplayHex<'a> {
+impl<'a> fmt::Display for GroupedHex<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl<'a> fmt::LowerHex for GroupedHex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+impl<'a> fmt::UpperHex for GroupedHex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, true)
+    }
+}
+
+/// Rendered with a leading `0x`, on a single line. Unlike [DisplayHex], long byte strings are never
+/// broken across lines.
+impl<'a> crate::prettier::PrettyPrint for GroupedHex<'a> {
     fn render(&self) -> crate::prettier::Document {
         crate::prettier::text(format!("{:#x}", self))
     }
 }
+
+/// Long byte strings are broken across lines in chunks of 32 bytes, or 16 or 8 bytes if those
+/// don't fit, with the digits of each line aligned with those of the first:
+///
+/// ```text
+/// 0x000102030405060708090a0b0c0d0e0f
+///   101112131415161718191a1b1c1d1e1f
+/// ```
+impl<'a> crate::prettier::PrettyPrint for DisplayHex<'a> {
+    fn render(&self) -> crate::prettier::Document {
+        use crate::prettier::{concat_vec, indent, nl, text};
+
+        let chunked = |size: usize| {
+            let mut chunks =
+                self.0.chunks(size).map(|chunk| text(format!("{:x}", DisplayHex(chunk))));
+            let first = chunks.next().unwrap_or_default();
+            let rest = concat_vec(chunks.map(|chunk| nl() + chunk).collect());
+            "0x" + first + indent(2, rest)
+        };
+        let single_line = text(format!("{:#x}", self));
+        // Prefer the largest chunks which fit, falling back to smaller ones
+        let broken = [32, 16, 8].into_iter().rev().filter(|size| self.0.len() > *size).fold(
+            None,
+            |fallback, size| match fallback {
+                None => Some(chunked(size)),
+                Some(fallback) => Some(chunked(size) | fallback),
+            },
+        );
+        match broken {
+            None => single_line,
+            Some(broken) => single_line | broken,
+        }
+    }
+}
+
+/// A wrapper for raw bytes which pretty-prints them as hexadecimal digits with a leading `0x`.
+///
+/// This is useful when pretty-printing fields which hold raw bytes, as `Vec<u8>` is otherwise
+/// rendered as a list of integers. Long byte strings are broken across lines like [DisplayHex].
+///
+/// NOTE: `[u8]` and `Vec<u8>` can't render as hex themselves, as that would conflict with the
+/// [crate::prettier::PrettyPrint] impls for slices and vectors of any element type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bytes<'a>(pub &'a [u8]);
+
+impl<'a> Bytes<'a> {
+    /// Pretty-print the underlying bytes of `item` as hexadecimal digits
+    #[inline]
+    pub fn new<'b: 'a, T>(item: &'b T) -> Self
+    where
+        T: ?Sized + AsRef<[u8]>,
+    {
+        Self(item.as_ref())
+    }
+}
+
+impl<'a> crate::prettier::PrettyPrint for Bytes<'a> {
+    fn render(&self) -> crate::prettier::Document {
+        DisplayHex(self.0).render()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array_to_hex() {
+        let bytes = [0xde_u8, 0xad, 0xbe, 0xef];
+        assert_eq!(bytes.to_hex(), "deadbeef");
+        assert_eq!(bytes.to_hex(), bytes[..].to_hex());
+        assert_eq!(bytes.to_hex_with_prefix(), "0xdeadbeef");
+        assert_eq!(bytes.to_hex_with_prefix(), bytes[..].to_hex_with_prefix());
+    }
+
+    /// A small deterministic pseudo-random number generator (xorshift64)
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn bytes(&mut self) -> Vec<u8> {
+            let len = (self.next() % 64) as usize;
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..1000 {
+            let bytes = rng.bytes();
+            assert_eq!(from_hex(&bytes.to_hex()).unwrap(), bytes);
+            assert_eq!(from_hex(&bytes.to_hex_with_prefix()).unwrap(), bytes);
+            assert_eq!(from_hex(&bytes.to_hex_upper()).unwrap(), bytes);
+            assert_eq!(from_hex(&bytes.to_hex_upper_with_prefix()).unwrap(), bytes);
+            let upper = bytes.to_hex_upper_with_upper_prefix();
+            assert!(upper.starts_with("0X"));
+            assert_eq!(from_hex(&upper).unwrap(), bytes);
+
+            // Randomly mix the case of the digits and the prefix
+            let mixed = bytes
+                .to_hex_with_prefix()
+                .chars()
+                .map(|c| if rng.next() % 2 == 0 { c.to_ascii_uppercase() } else { c })
+                .collect::<String>();
+            assert_eq!(from_hex(&mixed).unwrap(), bytes, "failed to decode {mixed}");
+        }
+    }
+
+    #[test]
+    fn fixed_size_hex_round_trips() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..1000 {
+            let bytes = (rng.next() as u128 | (rng.next() as u128) << 64).to_le_bytes();
+            assert_eq!(<[u8; 16]>::from_hex(&bytes.to_hex()), Ok(bytes));
+            assert_eq!(<[u8; 16]>::from_hex(&bytes.to_hex_upper_with_prefix()), Ok(bytes));
+            assert_eq!(<[u8; 16]>::from_hex(&bytes.to_hex_upper_with_upper_prefix()), Ok(bytes));
+            assert_eq!(
+                <[u8; 15]>::from_hex(&bytes.to_hex()),
+                Err(FromHexError::InvalidLength { expected: 15, actual: 16 })
+            );
+            // Dropping the last digit never silently truncates the input
+            let hex = bytes.to_hex();
+            assert_eq!(<[u8; 16]>::from_hex(&hex[..31]), Err(FromHexError::OddLength { len: 31 }));
+            assert_eq!(Vec::<u8>::from_hex(&hex[..31]), Err(FromHexError::OddLength { len: 31 }));
+        }
+    }
+
+    #[test]
+    fn hex_upper() {
+        let bytes = [0xde_u8, 0xad, 0xbe, 0xef];
+        assert_eq!(bytes.to_hex_upper(), "DEADBEEF");
+        assert_eq!(bytes.to_hex_upper_with_prefix(), "0xDEADBEEF");
+        assert_eq!(DisplayHex(&bytes).to_hex_upper_with_prefix(), "0xDEADBEEF");
+    }
+
+    #[test]
+    fn from_hex_errors() {
+        assert_eq!(<[u8; 2]>::from_hex("0XAaBb"), Ok([0xaa, 0xbb]));
+        assert_eq!(from_hex(""), Ok(vec![]));
+        assert_eq!(from_hex("0x"), Ok(vec![]));
+        assert_eq!(from_hex("0xabc"), Err(FromHexError::OddLength { len: 3 }));
+        assert_eq!(from_hex("0xabzd"), Err(FromHexError::InvalidDigit { offset: 4, found: 'z' }));
+        assert_eq!(from_hex("0x0x"), Err(FromHexError::InvalidDigit { offset: 3, found: 'x' }));
+        assert_eq!(from_hex("aé"), Err(FromHexError::InvalidDigit { offset: 1, found: 'é' }));
+        assert_eq!(
+            <[u8; 4]>::from_hex("aabb"),
+            Err(FromHexError::InvalidLength { expected: 4, actual: 2 })
+        );
+    }
+
+    #[test]
+    fn bytes_render_as_hex() {
+        use crate::prettier::PrettyPrint;
+
+        let bytes = alloc::vec![0xde_u8, 0xad, 0xbe, 0xef];
+        assert_eq!(Bytes::new(&bytes).to_pretty_string(), "0xdeadbeef");
+        assert_eq!(Bytes(&[]).to_pretty_string(), "0x");
+    }
+
+    #[test]
+    fn encode_iter_matches_to_hex() {
+        let bytes = (0..=255u8).collect::<alloc::vec::Vec<_>>();
+        assert_eq!(encode_iter(bytes.iter().copied()).collect::<String>(), bytes.to_hex());
+        assert_eq!(encode_iter([]).count(), 0);
+    }
+
+    #[test]
+    fn encode_iter_is_lazy() {
+        let mut consumed = 0;
+        let bytes = core::iter::repeat(0xab).inspect(|_| consumed += 1);
+        assert_eq!(encode_iter(bytes).take(5).collect::<String>(), "ababa");
+        assert_eq!(consumed, 3);
+    }
+
+    #[test]
+    fn encode_to_slice_buffers() {
+        let bytes = [0xde_u8, 0xad, 0xbe, 0xef];
+
+        let mut exact = [0u8; 8];
+        assert_eq!(encode_to_slice(&bytes, &mut exact), Ok(8));
+        assert_eq!(&exact, b"deadbeef");
+
+        let mut oversized = [b'-'; 12];
+        assert_eq!(encode_to_slice(&bytes, &mut oversized), Ok(8));
+        assert_eq!(&oversized, b"deadbeef----");
+
+        let mut small = [b'-'; 7];
+        assert_eq!(
+            encode_to_slice(&bytes, &mut small),
+            Err(BufferTooSmall { required: 8, actual: 7 })
+        );
+        assert_eq!(&small, b"-------");
+
+        assert_eq!(encode_to_slice(&[], &mut []), Ok(0));
+    }
+
+    #[test]
+    fn decode_to_slice_buffers() {
+        let digest = (0..32u8).map(|i| i.wrapping_mul(37)).collect::<Vec<_>>();
+        let mut decoded = [0u8; 32];
+        assert_eq!(decode_to_slice(&digest.to_hex_with_prefix(), &mut decoded), Ok(32));
+        assert_eq!(&decoded[..], &digest[..]);
+
+        let mut oversized = [0xffu8; 4];
+        assert_eq!(decode_to_slice("DEad", &mut oversized), Ok(2));
+        assert_eq!(oversized, [0xde, 0xad, 0xff, 0xff]);
+
+        let mut buf = [0u8; 4];
+        assert_eq!(decode_to_slice("abc", &mut buf), Err(FromHexError::OddLength { len: 3 }));
+        assert_eq!(
+            decode_to_slice("0xag", &mut buf),
+            Err(FromHexError::InvalidDigit { offset: 3, found: 'g' })
+        );
+        assert_eq!(
+            decode_to_slice("0102030405", &mut buf),
+            Err(FromHexError::BufferTooSmall(BufferTooSmall { required: 5, actual: 4 }))
+        );
+        assert_eq!(buf, [0; 4]);
+    }
+
+    #[test]
+    fn padded_hex() {
+        let bytes = [0x01_u8, 0xab];
+        let padded = DisplayHex::new(&bytes).with_min_digits(8);
+        assert_eq!(padded.to_hex(), "000001ab");
+        assert_eq!(padded.to_hex_with_prefix(), "0x000001ab");
+        assert_eq!(padded.to_hex_upper(), "000001AB");
+        assert_eq!(format!("{padded}"), "000001ab");
+        assert_eq!(format!("{padded:#X}"), "0x000001AB");
+        assert_eq!(DisplayHex(&[]).with_min_digits(64).to_hex(), "0".repeat(64));
+
+        // No truncation occurs when there are already enough digits
+        let unchanged = DisplayHex::new(&bytes).with_min_digits(4);
+        assert_eq!(unchanged.to_hex(), "01ab");
+        assert_eq!(DisplayHex::new(&bytes).with_min_digits(2).to_hex(), "01ab");
+        assert_eq!(DisplayHex::new(&bytes).with_min_digits(0).to_hex(), bytes.to_hex());
+    }
+
+    #[test]
+    fn bytes_break_in_chunks() {
+        use crate::prettier::PrettyPrint;
+
+        assert_eq!(Bytes(&[]).to_pretty_string_with_width(1), "0x");
+        assert_eq!(Bytes(&[0xde, 0xad, 0xbe, 0xef]).to_pretty_string_with_width(1), "0xdeadbeef");
+
+        let bytes = (0..200u8).collect::<Vec<_>>();
+        let bytes = Bytes::new(&bytes);
+        assert_eq!(bytes.to_pretty_string_with_width(500), bytes.0.to_hex_with_prefix());
+
+        // 32 byte chunks are too wide for 60 columns, so 16 byte chunks are used
+        let output = bytes.to_pretty_string_with_width(60);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], "0x000102030405060708090a0b0c0d0e0f");
+        assert_eq!(lines[1], "  101112131415161718191a1b1c1d1e1f");
+        assert_eq!(lines[12], "  c0c1c2c3c4c5c6c7");
+        assert_eq!(output.replace("\n  ", ""), bytes.0.to_hex_with_prefix());
+
+        let output = bytes.to_pretty_string_with_width(80);
+        assert_eq!(output.lines().count(), 7);
+        assert!(output.lines().all(|line| line.len() == 66 || line.len() == 18), "{output}");
+        assert_eq!(bytes.to_pretty_string_with_width(20).lines().count(), 25);
+    }
+
+    #[test]
+    fn from_hex_error_locations() {
+        let invalid = |offset, found| Err(HexError::InvalidDigit { offset, found });
+        // At the start, middle and end of the input, with and without a prefix
+        assert_eq!(from_hex("g0aabb"), invalid(0, 'g'));
+        assert_eq!(from_hex("0xg0aabb"), invalid(2, 'g'));
+        assert_eq!(from_hex("aa-bb"), invalid(2, '-'));
+        assert_eq!(from_hex("0Xaa bb"), invalid(4, ' '));
+        assert_eq!(from_hex("aabb\n"), invalid(4, '\n'));
+        assert_eq!(from_hex("0xaabbZ"), invalid(6, 'Z'));
+        // Offsets are in bytes, so they can be used to slice the input
+        let input = "0xé0aa";
+        let Err(HexError::InvalidDigit { offset, .. }) = from_hex(input) else {
+            panic!("expected an invalid digit");
+        };
+        assert_eq!(&input[offset..], "é0aa");
+        assert_eq!(from_hex("0xaaé"), invalid(4, 'é'));
+
+        // The unpaired digit of an odd-length input is the last one
+        assert_eq!(from_hex("a"), Err(FromHexError::OddLength { len: 1 }));
+        assert_eq!(from_hex("0xaabbc"), Err(FromHexError::OddLength { len: 5 }));
+        assert_eq!(
+            <[u8; 3]>::from_hex("0xaabb"),
+            Err(FromHexError::InvalidLength { expected: 3, actual: 2 })
+        );
+
+        assert_eq!(
+            format!("{}", from_hex("0xaa-b").unwrap_err()),
+            "invalid hexadecimal digit '-' at offset 4"
+        );
+        assert_eq!(
+            format!("{}", from_hex("0xabc").unwrap_err()),
+            "odd number of hexadecimal digits: 3"
+        );
+    }
+
+    #[test]
+    fn hex_writer_encodes_written_bytes() {
+        use core::fmt::Write;
+
+        let mut writer = HexWriter::new(String::from("0x"));
+        write!(writer, "{}-{:>4}", 255, "ab").unwrap();
+        assert_eq!(writer.get_ref(), "0x3235352d20206162");
+
+        // Multi-byte characters are encoded as their UTF-8 bytes, even when a character is
+        // written across more than one chunk of the internal buffer
+        let text = format!("{}é€", "x".repeat(31));
+        let mut writer = HexWriter::new(String::new());
+        writer.write_str(&text).unwrap();
+        writer.write_char('é').unwrap();
+        let expected = text.as_bytes().iter().chain("é".as_bytes()).copied().collect::<Vec<_>>();
+        assert_eq!(writer.into_inner(), expected.to_hex());
+
+        // References can be encoded like the values they refer to
+        fn encode<T: ToHex>(value: T) -> String {
+            value.to_hex_upper_with_prefix()
+        }
+        let bytes = [0xde_u8, 0xad];
+        assert_eq!(encode(&bytes[..]), "0xDEAD");
+        let slices: Vec<&[u8]> = alloc::vec![&bytes, &bytes[1..]];
+        assert_eq!(slices.iter().map(ToHex::to_hex).collect::<Vec<_>>(), ["dead", "ad"]);
+    }
+
+    #[test]
+    fn grouped_hex_separates_groups_of_bytes() {
+        let bytes = [0x7f, 0x3a, 0x22, 0xbc, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab];
+        let hex = || DisplayHex::new(&bytes);
+        assert_eq!(format!("{:x}", hex().group_bytes(1)), "7f 3a 22 bc 01 23 45 67 89 ab");
+        assert_eq!(
+            format!("{:#x}", hex().group_bytes(2).separator('_')),
+            "0x7f3a_22bc_0123_4567_89ab"
+        );
+        assert_eq!(format!("{:#X}", hex().group_bytes(8)), "0x7F3A22BC01234567 89AB");
+        assert_eq!(format!("{:x}", hex().group_bytes(16)), bytes.to_hex());
+        assert_eq!(format!("{:#x}", DisplayHex(&[]).group_bytes(2)), "0x");
+        let grouped = DisplayHex(&[0xab; 64]).group_bytes(4);
+        let output = crate::prettier::PrettyPrint::to_pretty_string_with_width(&grouped, 20);
+        assert_eq!(output.lines().count(), 1);
+    }
+}
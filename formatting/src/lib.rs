@@ -7,5 +7,9 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+// Allows the code generated by `#[derive(PrettyPrint)]` to be used within this crate
+#[cfg(feature = "derive")]
+extern crate self as miden_formatting;
+
 pub mod hex;
 pub mod prettier;
@@ -0,0 +1,275 @@
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    rc::Rc,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{cell::RefCell, fmt};
+
+use super::{
+    const_text, pretty_list, pretty_map, pretty_set, print, render_collection, shared_text,
+    Document, PrettyPrint,
+};
+
+/// The [PrettyPrintWith] trait is a companion to [PrettyPrint], for types which can only be
+/// rendered with the help of some context `C`, e.g. a string interner or symbol table.
+///
+/// The context is threaded through the containers in this crate, e.g. a `Vec<T>` can be rendered
+/// with a context `C` whenever `T` can, and laid out just like its [PrettyPrint] counterpart.
+///
+/// Primitive types, e.g. strings and integers, are rendered the same with any context. Other
+/// [PrettyPrint] types can be used where a [PrettyPrintWith] value is needed via [NoContext].
+/// There is no blanket implementation for all [PrettyPrint] types, as it would overlap with the
+/// implementations for containers.
+///
+/// # Example
+///
+/// ```rust
+/// use miden_formatting::prettier::*;
+///
+/// struct Interner(Vec<&'static str>);
+///
+/// struct Symbol(usize);
+///
+/// impl PrettyPrintWith<Interner> for Symbol {
+///     fn render_with(&self, interner: &Interner) -> Document {
+///         const_text(interner.0[self.0])
+///     }
+/// }
+///
+/// let interner = Interner(vec!["foo", "bar"]);
+/// let symbols = vec![Symbol(1), Symbol(0)];
+/// assert_eq!(symbols.to_pretty_string_with(&interner), "[bar, foo]");
+/// ```
+pub trait PrettyPrintWith<C: ?Sized> {
+    /// Render a [Document] representing the layout of this value, see [PrettyPrint::render].
+    ///
+    /// This method is the only one required to be implemented.
+    fn render_with(&self, ctx: &C) -> Document;
+
+    /// Produce a [String] containing the results of pretty-printing this object using `ctx`,
+    /// formatted with an assumed width of 80 columns, see [PrettyPrint::to_pretty_string].
+    fn to_pretty_string_with(&self, ctx: &C) -> String {
+        self.render_with(ctx).to_pretty_string()
+    }
+
+    /// Pretty-print this object to the given [core::fmt::Formatter] using `ctx`, see
+    /// [PrettyPrint::pretty_print].
+    fn pretty_print_with(&self, ctx: &C, f: &mut fmt::Formatter) -> fmt::Result {
+        let doc = self.render_with(ctx);
+        let width = f.width().unwrap_or(80);
+        print::pretty_print(&doc, width, f)
+    }
+}
+
+/// An adapter which renders any [PrettyPrint] value with any context, by ignoring the context.
+#[derive(Debug, Copy, Clone)]
+pub struct NoContext<'a, T: ?Sized>(pub &'a T);
+
+impl<'a, T: ?Sized + PrettyPrint, C: ?Sized> PrettyPrintWith<C> for NoContext<'a, T> {
+    #[inline]
+    fn render_with(&self, _ctx: &C) -> Document {
+        self.0.render()
+    }
+}
+
+/// A context which interns the text of the documents rendered with it, so that repeated strings,
+/// e.g. the identifiers of a large syntax tree, share a single allocation.
+///
+/// The first time a string is rendered using [RenderCtx::text], a reference-counted copy of it is
+/// stored in the context, and every document returned for an identical string shares that copy,
+/// see [super::shared_text], rather than copying the string again. The shared text is laid out
+/// exactly like [super::text].
+///
+/// Types which use the context implement [PrettyPrintWith] for it:
+///
+/// ```rust
+/// use miden_formatting::prettier::*;
+///
+/// struct Ident(String);
+///
+/// impl PrettyPrintWith<RenderCtx> for Ident {
+///     fn render_with(&self, ctx: &RenderCtx) -> Document {
+///         ctx.text(&self.0)
+///     }
+/// }
+///
+/// let ctx = RenderCtx::new();
+/// let idents = vec![Ident("x".into()), Ident("foo".into()), Ident("foo".into())];
+/// assert_eq!(idents.to_pretty_string_with(&ctx), "[x, foo, foo]");
+/// assert_eq!(ctx.len(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct RenderCtx {
+    strings: RefCell<BTreeSet<Rc<str>>>,
+}
+
+impl RenderCtx {
+    /// Create a new context, with no interned strings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a document displaying `s` exactly, like [super::text], sharing the text of any
+    /// previous document returned for the same string.
+    ///
+    /// Like [super::text], `s` is expected not to contain any newlines.
+    pub fn text(&self, s: &str) -> Document {
+        let interned = self.strings.borrow().get(s).cloned();
+        let interned = interned.unwrap_or_else(|| {
+            let interned = Rc::<str>::from(s);
+            self.strings.borrow_mut().insert(interned.clone());
+            interned
+        });
+        shared_text(interned)
+    }
+
+    /// Returns the number of distinct strings which have been interned
+    pub fn len(&self) -> usize {
+        self.strings.borrow().len()
+    }
+
+    /// Returns true if no strings have been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.borrow().is_empty()
+    }
+
+    /// Discard all of the interned strings, e.g. once the documents rendered using this context
+    /// have been printed
+    pub fn clear(&mut self) {
+        self.strings.get_mut().clear();
+    }
+}
+
+/// Implement [PrettyPrintWith] for types which render the same regardless of the context
+macro_rules! context_free {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl<C: ?Sized> PrettyPrintWith<C> for $ty {
+                #[inline]
+                fn render_with(&self, _ctx: &C) -> Document {
+                    PrettyPrint::render(self)
+                }
+            }
+        )*
+    };
+}
+
+context_free!(
+    str,
+    String,
+    Cow<'_, str>,
+    Document,
+    bool,
+    char,
+    (),
+    u8,
+    i8,
+    u16,
+    i16,
+    u32,
+    i32,
+    u64,
+    i64,
+    u128,
+    i128,
+    usize,
+    isize,
+    f32,
+    f64,
+);
+
+impl<T: ?Sized + PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for &T {
+    #[inline]
+    fn render_with(&self, ctx: &C) -> Document {
+        (**self).render_with(ctx)
+    }
+}
+
+impl<T: ?Sized + PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for Box<T> {
+    #[inline]
+    fn render_with(&self, ctx: &C) -> Document {
+        (**self).render_with(ctx)
+    }
+}
+
+impl<T: ?Sized + PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for Rc<T> {
+    #[inline]
+    fn render_with(&self, ctx: &C) -> Document {
+        (**self).render_with(ctx)
+    }
+}
+
+impl<T: ?Sized + PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for Arc<T> {
+    #[inline]
+    fn render_with(&self, ctx: &C) -> Document {
+        (**self).render_with(ctx)
+    }
+}
+
+/// Laid out like the [PrettyPrint] impl for [Option]
+impl<T: PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for Option<T> {
+    fn render_with(&self, ctx: &C) -> Document {
+        match self {
+            Some(value) => {
+                "some" + render_collection("(", ")", core::iter::once(value.render_with(ctx)))
+            },
+            None => const_text("none"),
+        }
+    }
+}
+
+/// Laid out like the [PrettyPrint] impl for [Result]
+impl<T: PrettyPrintWith<C>, E: PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for Result<T, E> {
+    fn render_with(&self, ctx: &C) -> Document {
+        match self {
+            Ok(value) => {
+                "ok" + render_collection("(", ")", core::iter::once(value.render_with(ctx)))
+            },
+            Err(error) => {
+                "err" + render_collection("(", ")", core::iter::once(error.render_with(ctx)))
+            },
+        }
+    }
+}
+
+impl<T: PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for [T] {
+    fn render_with(&self, ctx: &C) -> Document {
+        pretty_list(self.iter().map(|item| item.render_with(ctx)))
+    }
+}
+
+impl<T: PrettyPrintWith<C>, C: ?Sized, const N: usize> PrettyPrintWith<C> for [T; N] {
+    fn render_with(&self, ctx: &C) -> Document {
+        self.as_slice().render_with(ctx)
+    }
+}
+
+impl<T: PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for Vec<T> {
+    fn render_with(&self, ctx: &C) -> Document {
+        self.as_slice().render_with(ctx)
+    }
+}
+
+impl<T: PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for VecDeque<T> {
+    fn render_with(&self, ctx: &C) -> Document {
+        pretty_list(self.iter().map(|item| item.render_with(ctx)))
+    }
+}
+
+impl<T: PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C> for BTreeSet<T> {
+    fn render_with(&self, ctx: &C) -> Document {
+        pretty_set(self.iter().map(|item| item.render_with(ctx)))
+    }
+}
+
+impl<K: PrettyPrintWith<C>, V: PrettyPrintWith<C>, C: ?Sized> PrettyPrintWith<C>
+    for BTreeMap<K, V>
+{
+    fn render_with(&self, ctx: &C) -> Document {
+        pretty_map(self.iter().map(|(k, v)| (k.render_with(ctx), v.render_with(ctx))))
+    }
+}
@@ -1,13 +1,111 @@
 use alloc::{
     borrow::Cow,
+    collections::BTreeMap,
     rc::Rc,
     string::{String, ToString},
+    vec::Vec,
 };
 use core::fmt;
 
-#[derive(Debug, Default, Clone)]
+use super::{Measurement, PrettyPrint};
+
+/// A reference-counted string, or a slice of one, which can be cheaply cloned, see [shared_text]
+#[derive(Clone)]
+pub struct SharedStr {
+    buf: Rc<str>,
+    start: usize,
+    end: usize,
+}
+
+impl SharedStr {
+    /// Share all of `buf`
+    pub fn new(buf: Rc<str>) -> Self {
+        let end = buf.len();
+        Self { buf, start: 0, end }
+    }
+
+    /// Returns a [SharedStr] for the given byte range of this string, which shares its allocation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds, or does not lie on `char` boundaries, like indexing a
+    /// [str].
+    pub fn slice(&self, range: core::ops::Range<usize>) -> Self {
+        let _ = &self.as_str()[range.clone()];
+        Self {
+            buf: self.buf.clone(),
+            start: self.start + range.start,
+            end: self.start + range.end,
+        }
+    }
+
+    /// Returns the text of this string
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.buf[self.start..self.end]
+    }
+}
+
+impl core::ops::Deref for SharedStr {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SharedStr {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for SharedStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SharedStr {}
+
+impl fmt::Debug for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl From<Rc<str>> for SharedStr {
+    #[inline]
+    fn from(buf: Rc<str>) -> Self {
+        Self::new(buf)
+    }
+}
+
+impl From<String> for SharedStr {
+    #[inline]
+    fn from(s: String) -> Self {
+        Self::new(Rc::from(s))
+    }
+}
+
+impl From<&str> for SharedStr {
+    #[inline]
+    fn from(s: &str) -> Self {
+        Self::new(Rc::from(s))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub enum Document {
-    /// An empty document, rendered as an empty string
+    /// An empty document, rendered as an empty string, see [empty]
     #[default]
     Empty,
     /// A line break, rendered as a single '\n' char
@@ -19,6 +117,9 @@ pub enum Document {
     Char(char, u32),
     /// A literal text string of width `n`
     Text(Cow<'static, str>, u32),
+    /// A literal text string of width `n`, which shares its allocation with other documents, see
+    /// [shared_text]
+    SharedText(SharedStr, u32),
     /// A combinator which chooses the leftmost of each
     /// choice in the given document
     Flatten(Rc<Document>),
@@ -29,7 +130,58 @@ pub enum Document {
     /// Choose the more optimal of two documents depending on
     /// the amount of space remaining in the layout
     Choice(Rc<Document>, Rc<Document>),
+    /// Display the given document on a single line if it fits, otherwise display it as-is.
+    ///
+    /// If the group has an id, the decision made by the printer is recorded, so that it can be
+    /// referenced elsewhere in the document using [Document::IfBreak].
+    Group(Option<GroupId>, Rc<Document>),
+    /// Display the given document on a single line if it fits, otherwise use the multi-line
+    /// layout of every choice it contains.
+    ///
+    /// If the flag is set, the multi-line layout is always used, see [group_or_break].
+    ExpandGroup(bool, Rc<Document>),
+    /// Display the first document if the referenced group was broken across lines, otherwise
+    /// display the second document.
+    ///
+    /// If no group is referenced, the innermost enclosing group is used.
+    IfBreak(Option<GroupId>, Rc<Document>, Rc<Document>),
+    /// Emit the given text at the start of every line of the given document.
+    ///
+    /// If the flag is set, the text is emitted after the indentation of each line, otherwise it is
+    /// emitted before any indentation introduced within the document.
+    Prefix(Cow<'static, str>, bool, Rc<Document>),
+    /// Annotate the given document with an identifier, so that the region of the output it is
+    /// printed to can be recovered, see [super::render_with_spans].
+    Annotated(u64, Rc<Document>),
+    /// Display the given document on a single line, truncated to at most the given number of
+    /// columns, see [truncate].
+    Truncate(u32, Rc<Document>),
+    /// Pad the current line with spaces up to the given column, see [tabstop].
+    TabStop(u32),
+}
+
+/// A unique identifier for a group in a [Document].
+///
+/// Group identifiers are used to make layout decisions in one part of a document depend on the
+/// layout chosen for a group in another part of the document, see [group_with_id] and
+/// [if_group_breaks].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupId(u32);
+impl GroupId {
+    /// Create a new [GroupId] from a raw integer value.
+    ///
+    /// It is up to the caller to ensure that the value is unique within the document.
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    /// Get the raw integer value of this identifier
+    pub const fn as_u32(&self) -> u32 {
+        self.0
+    }
 }
+
 impl Document {
     /// Returns true if this document has no content, i.e. [Document::Empty]
     pub fn is_empty(&self) -> bool {
@@ -46,6 +198,7 @@ impl Document {
             Self::Char('\n' | '\r', _) => true,
             Self::Char(..) => false,
             Self::Text(ref text, _) => text.starts_with(['\n', '\r']),
+            Self::SharedText(ref text, _) => text.starts_with(['\n', '\r']),
             Self::Flatten(doc) => doc.has_leading_newline(),
             Self::Indent(_, doc) => doc.has_leading_newline(),
             Self::Concat(a, b) if a.is_empty() => b.has_leading_newline(),
@@ -53,7 +206,391 @@ impl Document {
             // The choice should always have a single-line option, so we
             // have to return false here
             Self::Choice(..) => false,
+            Self::Group(_, doc) | Self::ExpandGroup(_, doc) => doc.has_leading_newline(),
+            // For the same reason as choices, we must assume the flat layout is chosen
+            Self::IfBreak(..) => false,
+            Self::Prefix(..) => false,
+            Self::Annotated(_, doc) => doc.has_leading_newline(),
+            // Line breaks are never displayed by a truncated document
+            Self::Truncate(..) => false,
+            Self::TabStop(_) => false,
+        }
+    }
+
+    /// Returns the number of lines this document occupies when pretty-printed with a page
+    /// width of `width` columns.
+    ///
+    /// This runs the same layout algorithm used when printing, but only counts the line breaks
+    /// that are emitted, rather than rendering the document to a string. An empty document
+    /// occupies zero lines, otherwise the final line is counted even if it is empty, i.e. if
+    /// the output ends with a line break.
+    pub fn line_count_at(&self, width: usize) -> usize {
+        self.measure_at(width).lines
+    }
+
+    /// Returns the dimensions of the output of this document when pretty-printed with a page
+    /// width of `width` columns.
+    ///
+    /// Like [Document::line_count_at], this runs the same layout algorithm used when printing,
+    /// but only measures the output, rather than rendering the document to a string.
+    pub fn measure_at(&self, width: usize) -> Measurement {
+        super::print::measurement(self, &super::PrintConfig::new(width))
+    }
+
+    /// Describe the layout chosen for each group in this document when pretty-printed with a page
+    /// width of `width` columns, one line per group, in the order they are printed.
+    ///
+    /// This is intended for diagnosing unexpected layouts. For each group, the report gives the
+    /// line and column at which it starts, whether it was laid out flat or broken across lines,
+    /// and why, e.g. the width the line would have had if the group were flat. Lines and columns
+    /// are one-based.
+    pub fn explain_at(&self, width: usize) -> String {
+        super::print::explain(self, &super::PrintConfig::new(width))
+    }
+
+    /// Render the structure of this document as an indented tree, one node per line, e.g.:
+    ///
+    /// ```text
+    /// Concat
+    ///   Text("fn", w=2)
+    ///   Indent(4)
+    ///     Newline
+    /// ```
+    ///
+    /// This is intended for debugging [super::PrettyPrint::render] implementations. Nested
+    /// concatenations are shown as a single [Document::Concat] node, listing all of the documents
+    /// which are concatenated, regardless of how the concatenation was constructed.
+    pub fn debug_tree(&self) -> String {
+        use core::fmt::Write;
+
+        let mut tree = String::new();
+        let mut stack = vec![(self, 0)];
+        while let Some((doc, depth)) = stack.pop() {
+            if !tree.is_empty() {
+                tree.push('\n');
+            }
+            tree.extend(core::iter::repeat(' ').take(depth * 2));
+            let _ = match doc {
+                Self::Empty => write!(tree, "Empty"),
+                Self::Newline => write!(tree, "Newline"),
+                Self::Char(c, width) => write!(tree, "Char({c:?}, w={width})"),
+                Self::Text(text, width) => write!(tree, "Text({text:?}, w={width})"),
+                Self::SharedText(text, width) => write!(tree, "SharedText({text:?}, w={width})"),
+                Self::Flatten(_) => write!(tree, "Flatten"),
+                Self::Indent(indent, _) => write!(tree, "Indent({indent})"),
+                Self::Concat(..) => write!(tree, "Concat"),
+                Self::Choice(..) => write!(tree, "Choice"),
+                Self::Group(None, _) => write!(tree, "Group"),
+                Self::Group(Some(id), _) => write!(tree, "Group(id={})", id.as_u32()),
+                Self::ExpandGroup(false, _) => write!(tree, "ExpandGroup"),
+                Self::ExpandGroup(true, _) => write!(tree, "ExpandGroup(force_break)"),
+                Self::IfBreak(None, ..) => write!(tree, "IfBreak"),
+                Self::IfBreak(Some(id), ..) => write!(tree, "IfBreak(id={})", id.as_u32()),
+                Self::Prefix(prefix, false, _) => write!(tree, "Prefix({prefix:?})"),
+                Self::Prefix(prefix, true, _) => write!(tree, "Prefix({prefix:?}, after_indent)"),
+                Self::Annotated(id, _) => write!(tree, "Annotated({id})"),
+                Self::Truncate(max_width, _) => write!(tree, "Truncate({max_width})"),
+                Self::TabStop(column) => write!(tree, "TabStop({column})"),
+            };
+            match doc {
+                Self::Empty
+                | Self::Newline
+                | Self::Char(..)
+                | Self::Text(..)
+                | Self::SharedText(..)
+                | Self::TabStop(_) => (),
+                Self::Flatten(x)
+                | Self::Indent(_, x)
+                | Self::Group(_, x)
+                | Self::ExpandGroup(_, x)
+                | Self::Prefix(_, _, x)
+                | Self::Annotated(_, x)
+                | Self::Truncate(_, x) => stack.push((x, depth + 1)),
+                Self::Choice(x, y) | Self::IfBreak(_, x, y) => {
+                    stack.push((y, depth + 1));
+                    stack.push((x, depth + 1));
+                },
+                Self::Concat(..) => {
+                    // Collect the operands of nested concatenations, from last to first
+                    let mut operands = vec![doc];
+                    while let Some(operand) = operands.pop() {
+                        match operand {
+                            Self::Concat(x, y) => {
+                                operands.push(x);
+                                operands.push(y);
+                            },
+                            operand => stack.push((operand, depth + 1)),
+                        }
+                    }
+                },
+            }
+        }
+        tree
+    }
+
+    /// Shift the indentation of this document by `delta` columns.
+    ///
+    /// Every [Document::Indent] in the document has its width adjusted by `delta`, clamped at
+    /// zero. This is the inverse of [indent], in that it rebases the indentation of an existing
+    /// document, rather than adding a new level of indentation.
+    pub fn reindent(self, delta: i32) -> Document {
+        self.rebuild(|doc| match doc {
+            Self::Indent(i, x) => Self::Indent(i.saturating_add_signed(delta), x),
+            doc => doc,
+        })
+    }
+
+    /// Convert all of the text in this document to owned strings.
+    ///
+    /// Text is stored as a `Cow<'static, str>`, so borrowed text is already `'static`, but this
+    /// ensures that no part of the document refers to data it does not own, e.g. before it is
+    /// interned, or stored alongside documents built from owned text.
+    pub fn into_owned(self) -> Document {
+        fn owned(text: Cow<'static, str>) -> Cow<'static, str> {
+            Cow::Owned(text.into_owned())
+        }
+
+        self.rebuild(|doc| match doc {
+            Self::Text(text, width) => Self::Text(owned(text), width),
+            Self::Prefix(text, after_indent, x) => Self::Prefix(owned(text), after_indent, x),
+            doc => doc,
+        })
+    }
+
+    /// Rebuild this document from the bottom up, replacing every node with the result of `f`.
+    ///
+    /// The nodes of the document are visited in post-order, so the children of the node passed to
+    /// `f` have already been replaced. This is iterative, so it can be used on documents of any
+    /// depth. Subdocuments which are shared, e.g. between the layouts of a choice, are rebuilt
+    /// once, and the rebuilt subdocument is shared in the same way.
+    fn rebuild(&self, mut f: impl FnMut(Document) -> Document) -> Document {
+        enum Step<'a> {
+            Visit(&'a Rc<Document>),
+            Build(&'a Rc<Document>),
+        }
+
+        let mut rebuilt = BTreeMap::<*const Document, Rc<Document>>::new();
+        let mut steps =
+            self.children().into_iter().flatten().rev().map(Step::Visit).collect::<Vec<_>>();
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Visit(doc) if rebuilt.contains_key(&Rc::as_ptr(doc)) => (),
+                Step::Visit(doc) => {
+                    steps.push(Step::Build(doc));
+                    steps.extend(doc.children().into_iter().flatten().rev().map(Step::Visit));
+                },
+                // A shared subdocument may have been visited again before it was built
+                Step::Build(doc) if rebuilt.contains_key(&Rc::as_ptr(doc)) => (),
+                Step::Build(doc) => {
+                    let built = Rc::new(f(doc.with_children(&rebuilt)));
+                    rebuilt.insert(Rc::as_ptr(doc), built);
+                },
+            }
+        }
+        f(self.with_children(&rebuilt))
+    }
+
+    /// Returns the subdocuments of this document, from first to last
+    fn children(&self) -> [Option<&Rc<Document>>; 2] {
+        match self {
+            Self::Empty
+            | Self::Newline
+            | Self::Char(..)
+            | Self::Text(..)
+            | Self::SharedText(..)
+            | Self::TabStop(_) => [None, None],
+            Self::Flatten(x)
+            | Self::Indent(_, x)
+            | Self::Group(_, x)
+            | Self::ExpandGroup(_, x)
+            | Self::Prefix(_, _, x)
+            | Self::Annotated(_, x)
+            | Self::Truncate(_, x) => [Some(x), None],
+            Self::Concat(x, y) | Self::Choice(x, y) | Self::IfBreak(_, x, y) => [Some(x), Some(y)],
+        }
+    }
+
+    /// Returns a copy of this document, with each subdocument replaced by its entry in `rebuilt`
+    fn with_children(&self, rebuilt: &BTreeMap<*const Document, Rc<Document>>) -> Document {
+        let child = |x: &Rc<Document>| rebuilt[&Rc::as_ptr(x)].clone();
+        match self {
+            Self::Empty
+            | Self::Newline
+            | Self::Char(..)
+            | Self::Text(..)
+            | Self::SharedText(..)
+            | Self::TabStop(_) => self.clone(),
+            Self::Flatten(x) => Self::Flatten(child(x)),
+            Self::Indent(i, x) => Self::Indent(*i, child(x)),
+            Self::Group(id, x) => Self::Group(*id, child(x)),
+            Self::ExpandGroup(force_break, x) => Self::ExpandGroup(*force_break, child(x)),
+            Self::Prefix(prefix, after_indent, x) => {
+                Self::Prefix(prefix.clone(), *after_indent, child(x))
+            },
+            Self::Annotated(id, x) => Self::Annotated(*id, child(x)),
+            Self::Truncate(max_width, x) => Self::Truncate(*max_width, child(x)),
+            Self::Concat(x, y) => Self::Concat(child(x), child(y)),
+            Self::Choice(x, y) => Self::Choice(child(x), child(y)),
+            Self::IfBreak(id, x, y) => Self::IfBreak(*id, child(x), child(y)),
+        }
+    }
+
+    /// Returns the width of this document when laid out flat, i.e. the minimum page width at
+    /// which it is printed on a single line.
+    ///
+    /// Returns `None` if the flat layout contains a line break, see [Document::contains_hardline].
+    pub fn min_flat_width(&self) -> Option<u32> {
+        if self.contains_hardline() {
+            return None;
+        }
+        let config = super::PrintConfig::new(usize::MAX);
+        Some(super::print::measurement(self, &config).max_line_width as u32)
+    }
+
+    /// Returns the width of the last line of this document when laid out flat, i.e. the number
+    /// of columns following its last hard line break, or its entire width if it has none.
+    ///
+    /// This is useful for determining whether appending more content to this document would
+    /// overflow the current line.
+    pub fn trailing_width(&self) -> u32 {
+        let config = super::PrintConfig::new(usize::MAX);
+        super::print::measurement(&flatten(self.clone()), &config).final_column as u32
+    }
+
+    /// Returns the minimum page width at which this document is printed in at most `max_lines`
+    /// lines, or `None` if it cannot be printed in so few lines at any width.
+    ///
+    /// This searches for the width using the same layout algorithm used when printing, but
+    /// only measures the output, rather than rendering the document to a string.
+    pub fn min_width_for_lines(&self, max_lines: usize) -> Option<u32> {
+        // The layout chosen at any width at least as wide as the widest line of the layout
+        // chosen for an unbounded width is the same, so this bounds the search
+        let unbounded = self.measure_at(usize::MAX);
+        if unbounded.lines > max_lines {
+            return None;
+        }
+        let mut low = 0;
+        let mut high = unbounded.max_line_width;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.line_count_at(mid) <= max_lines {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Some(low as u32)
+    }
+
+    /// Render the first `max_lines` lines of this document, when pretty-printed with a page
+    /// width of `width` columns.
+    ///
+    /// Returns the rendered lines, without a trailing line break, along with a flag which is set
+    /// if any subsequent output was elided. The layout is chosen exactly as it would be when
+    /// rendering the entire document, so the result is always a prefix of the full rendering,
+    /// but printing stops as soon as the last line is complete.
+    pub fn render_preview(&self, width: usize, max_lines: usize) -> (String, bool) {
+        super::print::preview(self, &super::PrintConfig::new(width), max_lines)
+    }
+
+    /// Returns true if this document contains a hard line break, i.e. one that is displayed even
+    /// when the document is laid out flat.
+    ///
+    /// Only the flat layout of choices is considered, so line breaks which only occur in the
+    /// multi-line layout of a choice do not count. This can be used to check that a document
+    /// can be safely [flatten]ed.
+    pub fn contains_hardline(&self) -> bool {
+        let mut stack = vec![self];
+        while let Some(doc) = stack.pop() {
+            match doc {
+                Self::Newline | Self::Char('\n', _) => return true,
+                Self::Empty
+                | Self::Char(..)
+                | Self::Text(..)
+                | Self::SharedText(..)
+                | Self::TabStop(_) => (),
+                // Line breaks are never displayed by a truncated document
+                Self::Truncate(..) => (),
+                Self::Flatten(doc)
+                | Self::Indent(_, doc)
+                | Self::Group(_, doc)
+                | Self::ExpandGroup(_, doc)
+                | Self::Prefix(_, _, doc)
+                | Self::Annotated(_, doc) => stack.push(doc),
+                Self::Concat(a, b) => {
+                    stack.push(b);
+                    stack.push(a);
+                },
+                Self::Choice(flat, _) | Self::IfBreak(_, _, flat) => stack.push(flat),
+            }
         }
+        false
+    }
+
+    /// Returns the number of `char`s in the text of this document when laid out flat, counting
+    /// each line break as one.
+    ///
+    /// Unlike the width of a document, this does not depend on how wide each character is
+    /// displayed, so it can be used to bound the size of char-oriented output buffers. Output
+    /// which depends on where the document is printed, i.e. indentation, line prefixes and the
+    /// padding inserted by tab stops, is not counted. A [truncate]d document counts all of its
+    /// content, which is an upper bound on the number of chars it displays.
+    pub fn char_count_flat(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![self];
+        while let Some(doc) = stack.pop() {
+            match doc {
+                Self::Empty | Self::TabStop(_) => (),
+                Self::Newline | Self::Char(..) => count += 1,
+                Self::Text(text, _) => count += text.chars().count(),
+                Self::SharedText(text, _) => count += text.chars().count(),
+                Self::Flatten(doc)
+                | Self::Indent(_, doc)
+                | Self::Group(_, doc)
+                | Self::ExpandGroup(_, doc)
+                | Self::Prefix(_, _, doc)
+                | Self::Annotated(_, doc)
+                | Self::Truncate(_, doc) => stack.push(doc),
+                Self::Concat(a, b) => {
+                    stack.push(b);
+                    stack.push(a);
+                },
+                Self::Choice(flat, _) | Self::IfBreak(_, _, flat) => stack.push(flat),
+            }
+        }
+        count
+    }
+
+    /// Returns the text of this document when laid out flat, like [Document::char_count_flat].
+    ///
+    /// This is cheaper than printing the document, as no layout decisions are made, so it can be
+    /// used to order documents by their content, e.g. the elements of an unordered collection.
+    #[cfg(feature = "std")]
+    pub(crate) fn flat_text(&self) -> String {
+        let mut text = String::new();
+        let mut stack = vec![self];
+        while let Some(doc) = stack.pop() {
+            match doc {
+                Self::Empty | Self::TabStop(_) => (),
+                Self::Newline => text.push('\n'),
+                Self::Char(c, _) => text.push(*c),
+                Self::Text(s, _) => text.push_str(s),
+                Self::SharedText(s, _) => text.push_str(s),
+                Self::Flatten(doc)
+                | Self::Indent(_, doc)
+                | Self::Group(_, doc)
+                | Self::ExpandGroup(_, doc)
+                | Self::Prefix(_, _, doc)
+                | Self::Annotated(_, doc)
+                | Self::Truncate(_, doc) => stack.push(doc),
+                Self::Concat(a, b) => {
+                    stack.push(b);
+                    stack.push(a);
+                },
+                Self::Choice(flat, _) | Self::IfBreak(_, _, flat) => stack.push(flat),
+            }
+        }
+        text
     }
 }
 impl From<char> for Document {
@@ -68,6 +605,12 @@ impl From<&'static str> for Document {
         const_text(s)
     }
 }
+impl From<fmt::Arguments<'_>> for Document {
+    #[inline]
+    fn from(args: fmt::Arguments<'_>) -> Self {
+        format_doc(args)
+    }
+}
 impl From<String> for Document {
     #[inline(always)]
     fn from(s: String) -> Self {
@@ -75,11 +618,60 @@ impl From<String> for Document {
     }
 }
 
+/// An empty document, rendered as an empty string.
+///
+/// This is exactly [Document::Empty], which is also the [Default] document, so the three are
+/// interchangeable. In particular, the empty document is the identity of concatenation, so it can
+/// be used as the seed when folding documents together:
+///
+/// ```rust
+/// use miden_formatting::prettier::{empty, nl, text, Document, PrettyPrint};
+///
+/// struct Lines(Vec<&'static str>);
+///
+/// impl PrettyPrint for Lines {
+///     fn render(&self) -> Document {
+///         self.0.iter().fold(empty(), |acc, line| match acc {
+///             Document::Empty => text(*line),
+///             acc => acc + nl() + text(*line),
+///         })
+///     }
+/// }
+///
+/// assert_eq!(empty(), Document::Empty);
+/// assert_eq!(empty() + text("a"), text("a"));
+/// assert_eq!(Lines(vec!["a", "b"]).to_pretty_string(), "a\nb");
+/// assert_eq!(Lines(vec![]).to_pretty_string(), "");
+/// ```
+pub fn empty() -> Document {
+    Document::Empty
+}
+
 /// Render a line break (i.e. newline) in the output
 pub fn nl() -> Document {
     Document::Newline
 }
 
+/// A hard line break repeated `n` times, e.g. `blank_lines(2)` is a line break followed by one
+/// blank line, which is useful for separating paragraphs.
+///
+/// Like [nl], this is always displayed, has no width, and forces any enclosing group to break.
+/// Blank lines are never indented, and the line following them is indented like it would be
+/// following [nl]. If `n` is zero, this is the empty document.
+pub fn blank_lines(n: usize) -> Document {
+    concat_vec(vec![Document::Newline; n])
+}
+
+/// A line break which is displayed as `flat` when laid out on a single line.
+///
+/// This is a choice between `flat` and [nl], so it breaks whenever the rest of the line does not
+/// fit, or, within a [group_or_break], whenever the group breaks. For example, `line_or(" ")` is
+/// a line break which is a space when flat, and `line_or("")` one which disappears entirely.
+pub fn line_or(flat: &'static str) -> Document {
+    // This is constructed directly, as a choice with an empty option collapses to the other one
+    Document::Choice(Rc::new(const_text(flat)), Rc::new(nl()))
+}
+
 /// Display the given value using its [core::fmt::Display] implementation.
 ///
 /// This function expects that the display format does not contain any newlines. Violating this
@@ -89,12 +681,68 @@ pub fn display(s: impl ToString) -> Document {
     text(string)
 }
 
+/// Display the given value using its [core::fmt::Display] implementation, which may contain
+/// line breaks, unlike [display].
+///
+/// Each line break in the output is preserved as a [nl], like [split], so that every line is
+/// indented according to where the document is placed. The output is written to a single buffer,
+/// and the text of every line is a slice of that buffer, see [shared_text], rather than a copy.
+pub fn display_multiline(value: &impl fmt::Display) -> Document {
+    use core::fmt::Write;
+
+    let mut output = String::new();
+    write!(&mut output, "{value}").expect("writing to a string is infallible");
+    if !output.contains('\n') {
+        return owned_text(output);
+    }
+    let output = SharedStr::from(output);
+    join_lines(&output, |range| shared_text(output.slice(range)))
+}
+
+/// Pad the current line with spaces until it reaches `column`, or with a single space if it
+/// already extends to or past `column`.
+///
+/// Columns are counted from the start of the line, including any indentation, so this can be used
+/// to align the fields of consecutive lines into a table without measuring them in advance:
+///
+/// ```rust
+/// use miden_formatting::prettier::{concat_all, nl, tabstop, text, PrettyPrint};
+///
+/// let table = concat_all(&[
+///     text("x") + tabstop(6) + text("u32"),
+///     nl(),
+///     text("count") + tabstop(6) + text("usize"),
+/// ]);
+/// assert_eq!(format!("{}", table), "x     u32\ncount usize");
+/// ```
+pub fn tabstop(column: u32) -> Document {
+    Document::TabStop(column)
+}
+
+/// Display the given floating-point number, optionally with a fixed number of decimal places.
+///
+/// Without a precision, the shortest representation which parses back to exactly `value` is used,
+/// in scientific notation for very large or small magnitudes, e.g. `0.1`, `-0.0`, `1e-7`. With a
+/// precision, `value` is rounded to exactly that many decimal places, with ties rounded to even,
+/// e.g. `0.125` to two places is `0.12`. In either case, NaN and infinities are rendered as `NaN`,
+/// `inf` and `-inf`.
+///
+/// An `f32` can be printed with a fixed precision by converting it to `f64`, which is exact, so
+/// the result is the same.
+pub fn float(value: f64, precision: Option<usize>) -> Document {
+    match precision {
+        _ if !value.is_finite() => display(value),
+        None => text(format!("{value:?}")),
+        Some(precision) => text(format!("{value:.precision$}")),
+    }
+}
+
 /// Display the given character.
 pub fn character(c: char) -> Document {
     match c {
         '\n' => Document::Newline,
         c => {
-            let width = unicode_width::UnicodeWidthChar::width(c).unwrap_or(0) as u32;
+            let width = super::width::char_width(c) as u32;
             Document::Char(c, width)
         },
     }
@@ -105,19 +753,57 @@ pub fn character(c: char) -> Document {
 /// Like [display], this function expects the string does not contain any newlines. Violating this
 /// expectation may produce incorrect output.
 pub fn text(s: impl ToString) -> Document {
-    let string = Cow::<'static, str>::Owned(s.to_string());
+    owned_text(s.to_string())
+}
+
+/// Same as [text], but takes ownership of `string`, rather than copying it
+fn owned_text(string: String) -> Document {
+    let string = Cow::<'static, str>::Owned(string);
     let mut chars = string.chars();
     match chars.next() {
         None => Document::Empty,
         Some(c) if chars.next().is_none() => character(c),
         Some(_) => {
             drop(chars);
-            let width = unicode_width::UnicodeWidthStr::width(string.as_ref()) as u32;
+            let width = super::width::str_width(string.as_ref()) as u32;
             Document::Text(string, width)
         },
     }
 }
 
+/// Same as [text], but the text is shared with every other document built from the same
+/// [SharedStr], rather than copied, e.g. to render many occurrences of the same identifier.
+///
+/// Like [text], this function expects the string does not contain any newlines.
+pub fn shared_text(s: impl Into<SharedStr>) -> Document {
+    let string = s.into();
+    let mut chars = string.chars();
+    match chars.next() {
+        None => Document::Empty,
+        Some(c) if chars.next().is_none() => character(c),
+        Some(_) => {
+            drop(chars);
+            let width = super::width::str_width(&string) as u32;
+            Document::SharedText(string, width)
+        },
+    }
+}
+
+/// Same as [text], but for text containing ANSI escape sequences, e.g. to set the color of the
+/// output in a terminal.
+///
+/// The escape sequences are emitted as-is, but do not count towards the width of the text, so
+/// that colored output is laid out exactly like the equivalent uncolored output. Malformed or
+/// truncated escape sequences are considered to extend to the end of the text.
+pub fn ansi_text(s: impl ToString) -> Document {
+    let string = Cow::<'static, str>::Owned(s.to_string());
+    if string.is_empty() {
+        return Document::Empty;
+    }
+    let width = super::width::ansi_str_width(string.as_ref()) as u32;
+    Document::Text(string, width)
+}
+
 /// Same as [text], but for static/constant strings
 pub fn const_text(s: &'static str) -> Document {
     let mut chars = s.chars();
@@ -127,36 +813,177 @@ pub fn const_text(s: &'static str) -> Document {
         Some(_) => {
             drop(chars);
             let string = Cow::Borrowed(s);
-            let width = unicode_width::UnicodeWidthStr::width(string.as_ref()) as u32;
+            let width = super::width::str_width(string.as_ref()) as u32;
             Document::Text(string, width)
         },
     }
 }
 
 /// Create a document by splitting `input` on line breaks so ensure the invariants of [text] are upheld.
+///
+/// Each line break in `input` is preserved as a [nl], including those of blank lines, so that
+/// pre-formatted text renders with the same line structure as the original.
 pub fn split<S: AsRef<str>>(input: S) -> Document {
     let input = input.as_ref();
+    join_lines(input, |range| text(&input[range]))
+}
+
+/// Split `input` on line breaks, ignoring any `\r` before each one, and join the documents built
+/// by `line` from the byte range of each line with a [nl]
+fn join_lines(input: &str, mut line: impl FnMut(core::ops::Range<usize>) -> Document) -> Document {
+    let mut start = 0;
     input
-        .lines()
-        .map(text)
-        .reduce(|acc, doc| match acc {
-            Document::Empty => doc + nl(),
-            other => other + doc + nl(),
+        .split('\n')
+        .map(|text| {
+            let range = start..(start + text.strip_suffix('\r').unwrap_or(text).len());
+            start += text.len() + 1;
+            line(range)
         })
+        .reduce(|acc, line| acc + nl() + line)
         .unwrap_or(Document::Empty)
 }
 
+/// Create a document which word-wraps `input` to fit the page width.
+///
+/// Words are placed on the current line as long as they fit, and a line break is only inserted
+/// before a word which does not, so lines are filled greedily. Wrapped lines are indented like
+/// any other line, so the text is re-wrapped to fit whatever width is available where it is
+/// placed. A word wider than the page is placed on a line of its own, and never broken.
+///
+/// Words are separated by whitespace, and the whitespace itself is not preserved, i.e. runs of
+/// spaces, tabs and line breaks in `input` are all normalized to a single space, or a line break
+/// where the text is wrapped. Leading and trailing whitespace is removed.
+pub fn wrap_text(input: &str) -> Document {
+    let mut docs = Vec::new();
+    for word in input.split_whitespace() {
+        if !docs.is_empty() {
+            docs.push(character(' ') | nl());
+        }
+        docs.push(text(word));
+    }
+    concat_vec(docs)
+}
+
+/// Create a document from the output of [format_args], e.g. `format_doc(format_args!("{x}"))`.
+///
+/// The arguments are formatted directly into the text of the document, and if they are a string
+/// literal without any arguments, no allocation is needed at all. Line breaks in the output are
+/// preserved, see [split]. The [crate::doc_format] macro is a shorthand for this function.
+pub fn format_doc(args: fmt::Arguments<'_>) -> Document {
+    match args.as_str() {
+        Some(s) if s.contains('\n') => split(s),
+        Some(s) => const_text(s),
+        None => {
+            let string = alloc::fmt::format(args);
+            if string.contains('\n') {
+                split(string)
+            } else {
+                owned_text(string)
+            }
+        },
+    }
+}
+
 /// Concatenate two documents, producing a single document representing both.
 #[inline(always)]
 pub fn concat(left: Document, right: Document) -> Document {
     left + right
 }
 
+/// Concatenate all of `docs`, in order.
+///
+/// Unlike concatenating the documents one after another using `+`, which produces a tree of
+/// [Document::Concat] whose depth is the number of documents, this produces a balanced tree, whose
+/// depth is logarithmic in the number of documents. Prefer this when concatenating many documents.
+///
+/// Returns [Document::Empty] if `docs` is empty.
+pub fn concat_all(docs: &[Document]) -> Document {
+    concat_vec(docs.to_vec())
+}
+
+/// Same as [concat_all], but takes ownership of `docs`
+pub fn concat_vec(mut docs: Vec<Document>) -> Document {
+    // Concatenate adjacent pairs of documents until only one remains
+    while docs.len() > 1 {
+        let mut pairs = Vec::with_capacity(docs.len().div_ceil(2));
+        let mut docs_iter = docs.into_iter();
+        while let Some(left) = docs_iter.next() {
+            match docs_iter.next() {
+                Some(right) => pairs.push(left + right),
+                None => pairs.push(left),
+            }
+        }
+        docs = pairs;
+    }
+    docs.pop().unwrap_or_default()
+}
+
+/// Render each item of `items`, and concatenate the results, placing `sep` between each of them.
+///
+/// Returns [Document::Empty] if `items` is empty.
+pub fn join_iter<I>(sep: Document, items: I) -> Document
+where
+    I: IntoIterator,
+    I::Item: PrettyPrint,
+{
+    let mut docs = Vec::new();
+    for item in items {
+        if !docs.is_empty() {
+            docs.push(sep.clone());
+        }
+        docs.push(item.render());
+    }
+    concat_vec(docs)
+}
+
+/// Render a chain of method calls or field accesses on `receiver`, e.g. `a.b().c()`.
+///
+/// Each of `links` is preceded by a `.`, which should not be included in the link itself. The
+/// chain is laid out on one line if all of it fits, otherwise each link is placed on its own line,
+/// indented relative to the receiver:
+///
+/// ```text
+/// receiver
+///     .first()
+///     .second()
+/// ```
+///
+/// Returns `receiver` unchanged if `links` is empty.
+pub fn chain(receiver: Document, links: Vec<Document>) -> Document {
+    if links.is_empty() {
+        return receiver;
+    }
+    let flat = concat_vec(links.iter().map(|link| '.' + link.clone()).collect());
+    let broken = concat_vec(links.into_iter().map(|link| nl() + '.' + link).collect());
+    flatten(receiver.clone() + flat) | (receiver + indent(4, broken))
+}
+
+/// Render a labeled value, e.g. `key = value`.
+///
+/// The value is placed on the same line as the key if all of it fits there, otherwise it is placed
+/// on its own line, indented relative to the key, and laid out as it would be anywhere else:
+///
+/// ```text
+/// key =
+///     [
+///         value,
+///     ]
+/// ```
+pub fn key_value(key: impl Into<Document>, value: Document) -> Document {
+    let key = key.into();
+    flatten(key.clone() + " = " + value.clone()) | (key + " =" + indent(4, nl() + value))
+}
+
 /// Use the leftmost option of every choice in the given document.
 ///
 /// If the given document upholds the expectation that none of the
 /// leftmost choices contain newlines, then this combinator has the
 /// effect of displaying all choices on one line.
+///
+/// A hard line break (i.e. [nl]) in the flattened document is always
+/// honored, and the following line is indented at the current indentation
+/// level as usual. The remainder of the document continues to be laid out
+/// flat. Use [super::PrintConfig::with_strict_flatten] to detect this.
 pub fn flatten(doc: Document) -> Document {
     if doc.is_empty() {
         return doc;
@@ -177,6 +1004,120 @@ pub fn indent(indent: u32, doc: Document) -> Document {
     Document::Indent(indent, Rc::new(doc))
 }
 
+/// Display `doc` on a single line if it fits in the remaining space, otherwise display it as-is.
+///
+/// When the group fits, all choices within it use their leftmost option, just like [flatten].
+/// When it does not fit, the choices within it are made independently.
+pub fn group(doc: Document) -> Document {
+    if doc.is_empty() {
+        return doc;
+    }
+    Document::Group(None, Rc::new(doc))
+}
+
+/// Display `doc` on a single line if it fits in the remaining space, otherwise use the
+/// multi-line layout of every choice within it.
+///
+/// Unlike [group], the choices within a broken group are not made independently, so either all
+/// of them are broken, or none are. Nested groups are still laid out independently.
+///
+/// If `force_break` is set, the multi-line layout is always used, regardless of whether or not
+/// the single-line layout would fit, and any enclosing groups are broken as well. This is
+/// useful when the layout depends on some runtime condition, e.g. whether the source being
+/// printed had a trailing comma.
+pub fn group_or_break(doc: Document, force_break: bool) -> Document {
+    if doc.is_empty() {
+        return doc;
+    }
+    Document::ExpandGroup(force_break, Rc::new(doc))
+}
+
+/// Same as [group], but the group is associated with `id`.
+///
+/// The printer records whether or not a group was broken across lines, which can then be used
+/// to drive layout decisions elsewhere in the document using [if_group_breaks].
+pub fn group_with_id(id: GroupId, doc: Document) -> Document {
+    Document::Group(Some(id), Rc::new(doc))
+}
+
+/// Display `broken` if the enclosing group is broken across lines, otherwise display `flat`.
+///
+/// Outside of any group, `broken` is displayed, unless within a [flatten].
+pub fn if_break(broken: Document, flat: Document) -> Document {
+    Document::IfBreak(None, Rc::new(broken), Rc::new(flat))
+}
+
+/// Display `broken` if the group associated with `id` was broken across lines, otherwise display
+/// `flat`.
+///
+/// The group must be printed before this document for its layout to be known; if it has not
+/// been printed yet (or does not exist), the group is assumed to be flat.
+pub fn if_group_breaks(id: GroupId, broken: Document, flat: Document) -> Document {
+    Document::IfBreak(Some(id), Rc::new(broken), Rc::new(flat))
+}
+
+/// Emit `prefix` at the start of every line of `doc`, e.g. to render a block comment.
+///
+/// The prefix is emitted where `doc` begins, and following every line break within `doc`,
+/// including those introduced by the layout chosen for `doc` when printed. It is emitted at the
+/// indentation level in effect where `doc` begins, before any indentation introduced within
+/// `doc` itself, e.g.:
+///
+/// ```text
+/// // fn foo(
+/// //     a: number,
+/// // )
+/// ```
+///
+/// Use [prefix_lines_after_indent] if the prefix should follow the indentation instead.
+pub fn prefix_lines(prefix: &'static str, doc: Document) -> Document {
+    if doc.is_empty() {
+        return doc;
+    }
+    Document::Prefix(Cow::Borrowed(prefix), false, Rc::new(doc))
+}
+
+/// Same as [prefix_lines], but the prefix is emitted after the indentation of each line, e.g.:
+///
+/// ```text
+/// > fn foo(
+///     > a: number,
+/// > )
+/// ```
+pub fn prefix_lines_after_indent(prefix: &'static str, doc: Document) -> Document {
+    if doc.is_empty() {
+        return doc;
+    }
+    Document::Prefix(Cow::Borrowed(prefix), true, Rc::new(doc))
+}
+
+/// Display `doc` on a single line of at most `max_width` columns.
+///
+/// If `doc` would be wider than `max_width` columns, or contains a line break, it is cut short,
+/// and an ellipsis (`…`) is appended, such that the result, including the ellipsis, is no wider
+/// than `max_width` columns. Otherwise, `doc` is displayed unchanged.
+///
+/// The document is laid out as if the page were infinitely wide, and is otherwise printed as
+/// plain text, i.e. any annotations or group identifiers in it are ignored.
+pub fn truncate(max_width: u32, doc: Document) -> Document {
+    if doc.is_empty() {
+        return doc;
+    }
+    Document::Truncate(max_width, Rc::new(doc))
+}
+
+/// Annotate `doc` with `id`, so that the region of the output it is printed to can be recovered
+/// using [super::render_with_spans].
+///
+/// This is useful for mapping regions of the output back to the things they were rendered from,
+/// e.g. to highlight the relevant part of the output in diagnostics.
+pub fn with_span(id: u64, doc: Document) -> Document {
+    if doc.is_empty() {
+        return doc;
+    }
+    Document::Annotated(id, Rc::new(doc))
+}
+
 impl core::ops::Add for Document {
     type Output = Document;
 
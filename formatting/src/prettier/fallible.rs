@@ -0,0 +1,129 @@
+use alloc::{string::String, vec::Vec};
+use core::{convert::Infallible, fmt};
+
+use super::{
+    print, render_collection, render_list, render_map, Document, ListStyle, MapStyle, PrettyPrint,
+};
+
+/// The [TryPrettyPrint] trait is a companion to [PrettyPrint], for types whose rendering can fail,
+/// e.g. because an interned identifier cannot be resolved, or some lazily-loaded data is
+/// unavailable.
+///
+/// Every [PrettyPrint] type implements this trait, and never fails to render. As this blanket
+/// implementation would overlap with implementations for containers, fallible containers are
+/// rendered using helpers which stop at the first error, e.g. [try_pretty_list].
+///
+/// # Example
+///
+/// ```rust
+/// use miden_formatting::prettier::*;
+///
+/// struct Symbol(Option<&'static str>);
+///
+/// impl TryPrettyPrint for Symbol {
+///     type Error = &'static str;
+///
+///     fn try_render(&self) -> Result<Document, Self::Error> {
+///         self.0.map(const_text).ok_or("unknown symbol")
+///     }
+/// }
+///
+/// let symbols = vec![Symbol(Some("foo")), Symbol(None)];
+/// assert_eq!(try_pretty_list(&symbols[..1]).unwrap().to_pretty_string(), "[foo]");
+/// assert_eq!(try_pretty_list(&symbols).unwrap_err(), "unknown symbol");
+/// ```
+pub trait TryPrettyPrint {
+    /// The error produced when this value cannot be rendered
+    type Error;
+
+    /// Render a [Document] representing the layout of this value, see [PrettyPrint::render].
+    ///
+    /// This method is the only one required to be implemented.
+    fn try_render(&self) -> Result<Document, Self::Error>;
+
+    /// Produce a [String] containing the results of pretty-printing this object, formatted with
+    /// an assumed width of 80 columns, see [PrettyPrint::to_pretty_string].
+    fn try_to_pretty_string(&self) -> Result<String, Self::Error> {
+        self.try_render().map(|doc| doc.to_pretty_string())
+    }
+
+    /// Pretty-print this object to the given [core::fmt::Formatter], see
+    /// [PrettyPrint::pretty_print].
+    ///
+    /// If this object cannot be rendered, nothing is written to `f`, and the error is returned.
+    /// Otherwise, the result of writing to `f` is returned.
+    fn try_pretty_print(&self, f: &mut fmt::Formatter) -> Result<fmt::Result, Self::Error> {
+        let doc = self.try_render()?;
+        let width = f.width().unwrap_or(80);
+        Ok(print::pretty_print(&doc, width, f))
+    }
+}
+
+impl<T: ?Sized + PrettyPrint> TryPrettyPrint for T {
+    type Error = Infallible;
+
+    #[inline]
+    fn try_render(&self) -> Result<Document, Self::Error> {
+        Ok(self.render())
+    }
+}
+
+/// Render the items of `iter` as a list, like [super::pretty_list], or return the first error
+/// produced when rendering them.
+pub fn try_pretty_list<'a, I, T>(iter: I) -> Result<Document, T::Error>
+where
+    I: IntoIterator<Item = &'a T>,
+    T: ?Sized + TryPrettyPrint + 'a,
+{
+    try_pretty_list_with(iter, ListStyle::default())
+}
+
+/// Render the items of `iter` as a list laid out according to `style`, like
+/// [super::pretty_list_with], or return the first error produced when rendering them.
+pub fn try_pretty_list_with<'a, I, T>(iter: I, style: ListStyle) -> Result<Document, T::Error>
+where
+    I: IntoIterator<Item = &'a T>,
+    T: ?Sized + TryPrettyPrint + 'a,
+{
+    let items = iter.into_iter().map(T::try_render).collect::<Result<Vec<_>, _>>()?;
+    Ok(render_list(items.into_iter(), style))
+}
+
+/// Render the items of `iter` as a set, like [super::pretty_set], or return the first error
+/// produced when rendering them.
+pub fn try_pretty_set<'a, I, T>(iter: I) -> Result<Document, T::Error>
+where
+    I: IntoIterator<Item = &'a T>,
+    T: ?Sized + TryPrettyPrint + 'a,
+{
+    let items = iter.into_iter().map(T::try_render).collect::<Result<Vec<_>, _>>()?;
+    Ok(render_collection("{", "}", items.into_iter()))
+}
+
+/// Render the key/value pairs of `iter` as a map, like [super::pretty_map], or return the first
+/// error produced when rendering them.
+///
+/// The keys and values must produce the same type of error.
+pub fn try_pretty_map<'a, I, K, V>(iter: I) -> Result<Document, K::Error>
+where
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+    K: ?Sized + TryPrettyPrint + 'a,
+    V: ?Sized + TryPrettyPrint<Error = K::Error> + 'a,
+{
+    try_pretty_map_with(iter, MapStyle::default())
+}
+
+/// Render the key/value pairs of `iter` as a map laid out according to `style`, like
+/// [super::pretty_map_with], or return the first error produced when rendering them.
+pub fn try_pretty_map_with<'a, I, K, V>(iter: I, style: MapStyle) -> Result<Document, K::Error>
+where
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+    K: ?Sized + TryPrettyPrint + 'a,
+    V: ?Sized + TryPrettyPrint<Error = K::Error> + 'a,
+{
+    let entries = iter
+        .into_iter()
+        .map(|(k, v)| Ok((k.try_render()?, v.try_render()?)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(render_map(entries.into_iter(), style))
+}
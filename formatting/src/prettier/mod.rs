@@ -7,15 +7,42 @@
 //! etc.
 //!
 //! See the [PrettyPrint] trait for more on how to use this module.
+mod context;
 mod document;
+mod fallible;
 mod print;
+#[cfg(feature = "serde")]
+mod serde;
 #[cfg(test)]
 mod tests;
+mod width;
 
 use alloc::string::String;
 use core::fmt;
 
-pub use self::document::{concat, const_text, display, flatten, indent, nl, split, text, Document};
+#[cfg(feature = "derive")]
+pub use miden_formatting_derive::PrettyPrint;
+
+#[cfg(feature = "trace")]
+pub use self::print::{render_traced, Branch, TraceEvent};
+pub use self::{
+    context::{NoContext, PrettyPrintWith, RenderCtx},
+    document::{
+        ansi_text, blank_lines, chain, concat, concat_all, concat_vec, const_text, display,
+        display_multiline, empty, flatten, float, format_doc, group, group_or_break, group_with_id,
+        if_break, if_group_breaks, indent, join_iter, key_value, line_or, nl, prefix_lines,
+        prefix_lines_after_indent, shared_text, split, tabstop, text, truncate, with_span,
+        wrap_text, Document, GroupId, SharedStr,
+    },
+    fallible::{
+        try_pretty_list, try_pretty_list_with, try_pretty_map, try_pretty_map_with, try_pretty_set,
+        TryPrettyPrint,
+    },
+    print::{
+        pretty_print_to, render_checked, render_with, render_with_spans, Measurement, Overflow,
+        OverflowReport, PrintConfig, Printer, RenderEvent, SpanEntry,
+    },
+};
 
 /// The [PrettyPrint] trait is used as a building block for pretty printing data or syntax trees,
 /// as commonly seen in tools like Prettier.
@@ -157,21 +184,51 @@ pub trait PrettyPrint {
     /// indentation level, etc. Instead the focus is purely on the layout, leaving the heavy
     /// lifting to the pretty printer.
     ///
+    /// A fresh [Document] is returned on every call, and the caller owns it. Cloning a [Document]
+    /// is cheap, as nested documents are shared via [alloc::rc::Rc], but text held as an owned
+    /// [String] is copied, so prefer rendering once and reusing the result when the same value
+    /// is printed several times.
+    ///
     /// This method is the only one required to be implemented.
     fn render(&self) -> Document;
 
     /// Produce a [String] containing the results of pretty-printing this object.
     ///
     /// The string is formatted with an assumed width of 80 columns. If you wish to customize this,
-    /// you should instead prefer to use [PrettyPrint::pretty_print], or if you have implemented
-    /// [core::fmt::Display] for this type by delegating to [PrettyPrint::pretty_print], you can
-    /// use the Rust formatting syntax to do this, e.g. `format!("{:width$}", self, width = 100)`
+    /// use [PrettyPrint::to_pretty_string_with_width].
     fn to_pretty_string(&self) -> String {
-        format!("{:width$}", Prettier(self), width = 80)
+        self.to_pretty_string_with_width(80)
+    }
+
+    /// Produce a [String] containing the results of pretty-printing this object, formatted to fit
+    /// within `width` columns.
+    ///
+    /// Unlike `format!("{:width$}", self)`, this does not require [core::fmt::Display] to be
+    /// implemented in terms of [PrettyPrint::pretty_print].
+    ///
+    /// The string is allocated up front with enough capacity for the single-line layout of the
+    /// document produced by [PrettyPrint::render], so it only needs to grow if the document is
+    /// broken across lines.
+    ///
+    /// NOTE: The output is printed from [PrettyPrint::render] directly, not via
+    /// [PrettyPrint::pretty_print], so a type which overrides [PrettyPrint::pretty_print] to
+    /// produce different output must override this method as well to stay consistent. The
+    /// wrappers provided by this crate, e.g. `Box<T>`, delegate both methods to the wrapped value.
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        let doc = self.render();
+        let mut output = String::with_capacity(print::capacity_hint(&doc));
+        pretty_print_to(&doc, width, &mut output).expect("writing to a string is infallible");
+        output
     }
 
     /// Pretty-print this object to the given [core::fmt::Formatter].
     ///
+    /// The width of the formatter, if set, is used as the page width, otherwise it defaults to
+    /// 80 columns. The precision of the formatter, if set, is used as the maximum number of lines
+    /// to print, e.g. `{:80.40}` prints at most 40 lines of 80 columns, see
+    /// [PrintConfig::with_max_lines]. The alternate flag, i.e. `{:#}`, requests the fully
+    /// expanded multi-line layout, see [PrintConfig::with_expand_all].
+    ///
     /// You may implement [core::fmt::Display] for your type in terms of this function like so:
     ///
     /// ```rust,ignore
@@ -186,6 +243,55 @@ pub trait PrettyPrint {
         let width = f.width().unwrap_or(80);
         print::pretty_print(&doc, width, f)
     }
+
+    /// Produce a [String] containing the results of pretty-printing this object using `config`.
+    ///
+    /// See [PrintConfig] for the available options.
+    fn to_pretty_string_with_config(&self, config: &PrintConfig) -> String {
+        let mut output = String::new();
+        self.pretty_print_with_config(config, &mut output)
+            .expect("writing to a string is infallible");
+        output
+    }
+
+    /// Pretty-print this object to `f`, using `config` rather than the width of a
+    /// [core::fmt::Formatter] to drive the layout.
+    fn pretty_print_with_config(
+        &self,
+        config: &PrintConfig,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        let doc = self.render();
+        print::pretty_print_with_config(&doc, config, f)
+    }
+
+    /// Pretty-print this object to `f` on a page of `width` columns, as if it were embedded in
+    /// text which is already indented by `base` columns.
+    ///
+    /// The first line is assumed to start at column `base`, and every subsequent line is indented
+    /// by at least `base` columns. Unlike wrapping the document in [indent], the printer knows
+    /// that the first line starts at `base`, so layouts which would overflow it are avoided, see
+    /// [PrintConfig::with_initial_indent].
+    fn pretty_print_with_base_indent(
+        &self,
+        f: &mut dyn fmt::Write,
+        width: usize,
+        base: u32,
+    ) -> fmt::Result {
+        let config = PrintConfig::new(width).with_initial_indent(base);
+        self.pretty_print_with_config(&config, f)
+    }
+
+    /// Produce a [String] containing the results of pretty-printing this object on a page of
+    /// `width` columns, with every line following the first indented by at least `base` columns.
+    ///
+    /// See [PrettyPrint::pretty_print_with_base_indent].
+    fn to_pretty_string_with_base_indent(&self, width: usize, base: u32) -> String {
+        let mut output = String::new();
+        self.pretty_print_with_base_indent(&mut output, width, base)
+            .expect("writing to a string is infallible");
+        output
+    }
 }
 
 impl fmt::Display for dyn PrettyPrint {
@@ -194,19 +300,143 @@ impl fmt::Display for dyn PrettyPrint {
     }
 }
 
+impl fmt::Display for dyn PrettyPrint + Send {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        PrettyPrint::pretty_print(self, f)
+    }
+}
+
+impl fmt::Display for dyn PrettyPrint + Send + Sync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        PrettyPrint::pretty_print(self, f)
+    }
+}
+
+/// An adapter which displays the wrapped value using [PrettyPrint::pretty_print], see [pretty].
+///
+/// The width, precision and alternate flag of the formatter are all forwarded, so this is useful
+/// for values whose own [core::fmt::Display] implementation is not their pretty-printed form.
+#[derive(Debug, Copy, Clone)]
+pub struct Pretty<'a, P: ?Sized + PrettyPrint>(pub &'a P);
+
+impl<'a, P: ?Sized + PrettyPrint> fmt::Display for Pretty<'a, P> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.pretty_print(f)
+    }
+}
+
+/// Display `value` using [PrettyPrint::pretty_print], regardless of its own [core::fmt::Display]
+/// implementation, if any.
+///
+/// ```rust
+/// use miden_formatting::prettier::pretty;
+///
+/// let items = vec![1, 2, 3];
+/// println!("{:100}", pretty(&items));
+/// assert_eq!(format!("{}", pretty(&items)), "[1, 2, 3]");
+/// // A narrow width, or the alternate flag, breaks the list across lines
+/// assert_eq!(format!("{:5}", pretty(&items)), "[\n    1,\n    2,\n    3\n]");
+/// assert_eq!(format!("{:#}", pretty(&items)), format!("{:5}", pretty(&items)));
+/// ```
+#[inline]
+pub fn pretty<P: ?Sized + PrettyPrint>(value: &P) -> Pretty<'_, P> {
+    Pretty(value)
+}
+
+/// A wrapper which memoizes the [Document] rendered for the wrapped value.
+///
+/// The value is rendered the first time the cell is rendered or displayed, and the cached
+/// document is reused afterwards. Cloning a [Document] is cheap, so this avoids rebuilding large
+/// documents for values which are displayed repeatedly, e.g. an error which is logged and then
+/// reported. If the value is modified, the cached document must be discarded, which
+/// [PrettyCell::get_mut] does automatically, see also [PrettyCell::invalidate].
+///
+/// ```rust
+/// use miden_formatting::prettier::PrettyCell;
+///
+/// let mut cell = PrettyCell::new(vec![1, 2]);
+/// assert_eq!(cell.to_string(), "[1, 2]");
+/// cell.get_mut().push(3);
+/// assert_eq!(cell.to_string(), "[1, 2, 3]");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PrettyCell<T> {
+    value: T,
+    doc: core::cell::OnceCell<Document>,
+}
+
+impl<T: PrettyPrint> PrettyCell<T> {
+    /// Wrap `value`, which is not rendered until it is first needed
+    pub fn new(value: T) -> Self {
+        Self { value, doc: core::cell::OnceCell::new() }
+    }
+
+    /// Get a reference to the wrapped value
+    #[inline]
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Get a mutable reference to the wrapped value, discarding the cached document, if any
+    pub fn get_mut(&mut self) -> &mut T {
+        self.invalidate();
+        &mut self.value
+    }
+
+    /// Discard the cached document, if any, so that the value is rendered again when next needed.
+    ///
+    /// This is only necessary if the rendering of the value can change without going through
+    /// [PrettyCell::get_mut], e.g. due to interior mutability.
+    pub fn invalidate(&mut self) {
+        self.doc.take();
+    }
+
+    /// Unwrap the value, discarding the cached document, if any
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for PrettyCell<T> {
+    fn render(&self) -> Document {
+        self.doc.get_or_init(|| self.value.render()).clone()
+    }
+}
+
+impl<T: PrettyPrint> fmt::Display for PrettyCell<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.pretty_print(f)
+    }
+}
+
 /// Generate an implementation of [PrettyPrint] for a given type by delegating to [core::fmt::Display].
 ///
+/// Generic types are supported by listing the generic parameters of the impl in brackets,
+/// optionally followed by a `where` clause.
+///
 /// # Example
 ///
 /// ```rust,ignore
-/// pretty_via_to_display!(Foo);
+/// pretty_via_display!(Foo);
+/// pretty_via_display!(impl[T: Display] for Wrapper<T>);
+/// pretty_via_display!(impl['a, T] for Borrowed<'a, T> where T: Display);
 /// ```
 #[macro_export]
 macro_rules! pretty_via_display {
     ($name:ty) => {
         impl $crate::prettier::PrettyPrint for $name {
             fn render(&self) -> $crate::prettier::Document {
-                $crate::prettier::display(*self)
+                $crate::prettier::display(self)
+            }
+        }
+    };
+
+    (impl [$($generics:tt)*] for $name:ty $(where $($bounds:tt)+)?) => {
+        impl<$($generics)*> $crate::prettier::PrettyPrint for $name $(where $($bounds)+)? {
+            fn render(&self) -> $crate::prettier::Document {
+                $crate::prettier::display(self)
             }
         }
     };
@@ -214,10 +444,13 @@ macro_rules! pretty_via_display {
 
 /// Generate an implementation of [PrettyPrint] for a given type by delegating to [alloc::string::ToString].
 ///
+/// Generic types are supported using the same syntax as [pretty_via_display].
+///
 /// # Example
 ///
 /// ```rust,ignore
 /// pretty_via_to_string!(Foo);
+/// pretty_via_to_string!(impl['a] for Name<'a>);
 /// ```
 #[macro_export]
 macro_rules! pretty_via_to_string {
@@ -228,6 +461,77 @@ macro_rules! pretty_via_to_string {
             }
         }
     };
+
+    (impl [$($generics:tt)*] for $name:ty $(where $($bounds:tt)+)?) => {
+        impl<$($generics)*> $crate::prettier::PrettyPrint for $name $(where $($bounds)+)? {
+            fn render(&self) -> $crate::prettier::Document {
+                $crate::prettier::text(&**self)
+            }
+        }
+    };
+}
+
+/// Create a [Document] using the same syntax as [format], see [format_doc].
+///
+/// # Example
+///
+/// ```rust
+/// use miden_formatting::{doc_format, prettier::PrettyPrint};
+///
+/// let name = "main";
+/// let doc = doc_format!("fn {name}()");
+/// assert_eq!(doc.to_pretty_string(), "fn main()");
+/// ```
+///
+/// [format]: alloc::format
+#[macro_export]
+macro_rules! doc_format {
+    ($($arg:tt)*) => {
+        $crate::prettier::format_doc(::core::format_args!($($arg)*))
+    };
+}
+
+/// Generate an implementation of [PrettyPrint] for a given type by delegating to [core::fmt::Debug].
+///
+/// By default, the value is formatted using `{:?}`. Use `pretty_via_debug!(Foo, alternate)` to
+/// format it using `{:#?}` instead. Any line breaks in the output are preserved, see [split].
+///
+/// # Example
+///
+/// ```rust,ignore
+/// pretty_via_debug!(Foo);
+/// pretty_via_debug!(Bar, alternate);
+/// ```
+#[macro_export]
+macro_rules! pretty_via_debug {
+    ($name:ty) => {
+        impl $crate::prettier::PrettyPrint for $name {
+            fn render(&self) -> $crate::prettier::Document {
+                $crate::prettier::__render_debug(self, false)
+            }
+        }
+    };
+
+    ($name:ty, alternate) => {
+        impl $crate::prettier::PrettyPrint for $name {
+            fn render(&self) -> $crate::prettier::Document {
+                $crate::prettier::__render_debug(self, true)
+            }
+        }
+    };
+}
+
+/// Render `value` using its [core::fmt::Debug] implementation, see [pretty_via_debug].
+///
+/// This is an implementation detail of the macro, which avoids the need for callers to have
+/// `format!` in scope.
+#[doc(hidden)]
+pub fn __render_debug(value: &dyn fmt::Debug, alternate: bool) -> Document {
+    if alternate {
+        split(format!("{value:#?}"))
+    } else {
+        split(format!("{value:?}"))
+    }
 }
 
 pretty_via_display!(bool);
@@ -255,8 +559,136 @@ pretty_via_display!(core::num::NonZeroU128);
 pretty_via_display!(core::num::NonZeroI128);
 pretty_via_display!(core::num::NonZeroUsize);
 pretty_via_display!(core::num::NonZeroIsize);
+// NOTE: `core::net` is only available since Rust 1.77, which is newer than our MSRV, so it must
+// be opted into using the `core-net` feature. The types in `std::net` are the same types, so the
+// impls are only provided once.
+#[cfg(feature = "core-net")]
+pretty_via_display!(core::net::IpAddr);
+#[cfg(feature = "core-net")]
+pretty_via_display!(core::net::Ipv4Addr);
+#[cfg(feature = "core-net")]
+pretty_via_display!(core::net::Ipv6Addr);
+#[cfg(feature = "core-net")]
+pretty_via_display!(core::net::SocketAddr);
+#[cfg(feature = "core-net")]
+pretty_via_display!(core::net::SocketAddrV4);
+#[cfg(feature = "core-net")]
+pretty_via_display!(core::net::SocketAddrV6);
+#[cfg(all(feature = "std", not(feature = "core-net")))]
+pretty_via_display!(std::net::IpAddr);
+#[cfg(all(feature = "std", not(feature = "core-net")))]
+pretty_via_display!(std::net::Ipv4Addr);
+#[cfg(all(feature = "std", not(feature = "core-net")))]
+pretty_via_display!(std::net::Ipv6Addr);
+#[cfg(all(feature = "std", not(feature = "core-net")))]
+pretty_via_display!(std::net::SocketAddr);
+#[cfg(all(feature = "std", not(feature = "core-net")))]
+pretty_via_display!(std::net::SocketAddrV4);
+#[cfg(all(feature = "std", not(feature = "core-net")))]
+pretty_via_display!(std::net::SocketAddrV6);
+
+/// Floating-point numbers are rendered using their shortest exact representation, see [float]
+impl PrettyPrint for f32 {
+    fn render(&self) -> Document {
+        if self.is_finite() {
+            text(format!("{self:?}"))
+        } else {
+            display(*self)
+        }
+    }
+}
+
+/// Floating-point numbers are rendered using their shortest exact representation, see [float]
+impl PrettyPrint for f64 {
+    fn render(&self) -> Document {
+        float(*self, None)
+    }
+}
+
+/// A `'\n'` is rendered as a line break, like any other newline, see [Document::Newline]
+impl PrettyPrint for char {
+    fn render(&self) -> Document {
+        Document::from(*self)
+    }
+}
+
+/// The unit type is rendered as `()`, like its [fmt::Debug] implementation, so that it remains
+/// visible when nested in other values, e.g. `ok(())`
+impl PrettyPrint for () {
+    fn render(&self) -> Document {
+        const_text("()")
+    }
+}
+
+/// A [Document] renders as itself, so already-built documents can be used wherever a
+/// [PrettyPrint] value is expected, e.g. as the elements of a `Vec<Document>`
+impl PrettyPrint for Document {
+    #[inline]
+    fn render(&self) -> Document {
+        self.clone()
+    }
+}
+
+/// Atomic integers are rendered as their current value, loaded using [Ordering::Relaxed].
+///
+/// Rendering only needs the value itself, not to synchronize with other memory accesses, so the
+/// weakest ordering is used. The value may be stale as soon as it is loaded in any case.
+///
+/// [Ordering::Relaxed]: core::sync::atomic::Ordering::Relaxed
+macro_rules! pretty_via_atomic {
+    ($size:literal, $name:ident) => {
+        #[cfg(target_has_atomic = $size)]
+        impl PrettyPrint for core::sync::atomic::$name {
+            fn render(&self) -> Document {
+                display(self.load(core::sync::atomic::Ordering::Relaxed))
+            }
+        }
+    };
+}
+
+pretty_via_atomic!("8", AtomicU8);
+pretty_via_atomic!("8", AtomicI8);
+pretty_via_atomic!("16", AtomicU16);
+pretty_via_atomic!("16", AtomicI16);
+pretty_via_atomic!("32", AtomicU32);
+pretty_via_atomic!("32", AtomicI32);
+pretty_via_atomic!("64", AtomicU64);
+pretty_via_atomic!("64", AtomicI64);
+pretty_via_atomic!("ptr", AtomicUsize);
+pretty_via_atomic!("ptr", AtomicIsize);
+
+impl<T: ?Sized + PrettyPrint> PrettyPrint for &T {
+    #[inline]
+    fn render(&self) -> Document {
+        (**self).render()
+    }
+    #[inline]
+    fn to_pretty_string(&self) -> String {
+        (**self).to_pretty_string()
+    }
+    #[inline]
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        (**self).to_pretty_string_with_width(width)
+    }
+    #[inline]
+    fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).pretty_print(f)
+    }
+    #[inline]
+    fn to_pretty_string_with_config(&self, config: &PrintConfig) -> String {
+        (**self).to_pretty_string_with_config(config)
+    }
+    #[inline]
+    fn pretty_print_with_config(
+        &self,
+        config: &PrintConfig,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        (**self).pretty_print_with_config(config, f)
+    }
+}
 
-impl<'a, T: ?Sized + PrettyPrint> PrettyPrint for &'a T {
+impl<T: ?Sized + PrettyPrint> PrettyPrint for &mut T {
     #[inline]
     fn render(&self) -> Document {
         (**self).render()
@@ -266,9 +698,25 @@ impl<'a, T: ?Sized + PrettyPrint> PrettyPrint for &'a T {
         (**self).to_pretty_string()
     }
     #[inline]
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        (**self).to_pretty_string_with_width(width)
+    }
+    #[inline]
     fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
         (**self).pretty_print(f)
     }
+    #[inline]
+    fn to_pretty_string_with_config(&self, config: &PrintConfig) -> String {
+        (**self).to_pretty_string_with_config(config)
+    }
+    #[inline]
+    fn pretty_print_with_config(
+        &self,
+        config: &PrintConfig,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        (**self).pretty_print_with_config(config, f)
+    }
 }
 
 impl PrettyPrint for str {
@@ -287,6 +735,9 @@ impl PrettyPrint for String {
     fn render(&self) -> Document {
         PrettyPrint::render(self.as_str())
     }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        PrettyPrint::to_pretty_string_with_width(self.as_str(), width)
+    }
     fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
         PrettyPrint::pretty_print(self.as_str(), f)
     }
@@ -296,90 +747,686 @@ impl<'a> PrettyPrint for alloc::borrow::Cow<'a, str> {
     fn render(&self) -> Document {
         PrettyPrint::render(self.as_ref())
     }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        PrettyPrint::to_pretty_string_with_width(self.as_ref(), width)
+    }
     fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
         PrettyPrint::pretty_print(self.as_ref(), f)
     }
 }
 
-impl<T: PrettyPrint> PrettyPrint for alloc::boxed::Box<T> {
+/// Smart pointers delegate to the value they point to, which may be unsized, e.g. a
+/// `Box<dyn PrettyPrint>`, so that heterogeneous values can be rendered together
+impl<T: ?Sized + PrettyPrint> PrettyPrint for alloc::boxed::Box<T> {
     fn render(&self) -> Document {
         PrettyPrint::render(self.as_ref())
     }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        PrettyPrint::to_pretty_string_with_width(self.as_ref(), width)
+    }
     fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
         PrettyPrint::pretty_print(self.as_ref(), f)
     }
 }
 
-impl<T: PrettyPrint> PrettyPrint for alloc::rc::Rc<T> {
+impl<T: ?Sized + PrettyPrint> PrettyPrint for alloc::rc::Rc<T> {
     fn render(&self) -> Document {
         PrettyPrint::render(self.as_ref())
     }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        PrettyPrint::to_pretty_string_with_width(self.as_ref(), width)
+    }
     fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
         PrettyPrint::pretty_print(self.as_ref(), f)
     }
 }
 
-impl<T: PrettyPrint> PrettyPrint for alloc::sync::Arc<T> {
+impl<T: ?Sized + PrettyPrint> PrettyPrint for alloc::sync::Arc<T> {
     fn render(&self) -> Document {
         PrettyPrint::render(self.as_ref())
     }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        PrettyPrint::to_pretty_string_with_width(self.as_ref(), width)
+    }
     fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
         PrettyPrint::pretty_print(self.as_ref(), f)
     }
 }
 
+impl<T: Copy + PrettyPrint> PrettyPrint for core::cell::Cell<T> {
+    #[inline]
+    fn render(&self) -> Document {
+        self.get().render()
+    }
+    #[inline]
+    fn to_pretty_string(&self) -> String {
+        self.get().to_pretty_string()
+    }
+    #[inline]
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        self.get().to_pretty_string_with_width(width)
+    }
+    #[inline]
+    fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get().pretty_print(f)
+    }
+    #[inline]
+    fn to_pretty_string_with_config(&self, config: &PrintConfig) -> String {
+        self.get().to_pretty_string_with_config(config)
+    }
+    #[inline]
+    fn pretty_print_with_config(
+        &self,
+        config: &PrintConfig,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        self.get().pretty_print_with_config(config, f)
+    }
+}
+
+/// A [core::cell::RefCell] is borrowed while it is printed, and delegates to the borrowed value.
+///
+/// This never panics: if the cell is already mutably borrowed, e.g. when printing it from within
+/// code which is modifying it, `<borrowed>` is printed instead.
+impl<T: PrettyPrint> PrettyPrint for core::cell::RefCell<T> {
+    fn render(&self) -> Document {
+        match self.try_borrow() {
+            Ok(value) => value.render(),
+            Err(_) => const_text("<borrowed>"),
+        }
+    }
+    fn to_pretty_string(&self) -> String {
+        match self.try_borrow() {
+            Ok(value) => value.to_pretty_string(),
+            Err(_) => String::from("<borrowed>"),
+        }
+    }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        match self.try_borrow() {
+            Ok(value) => value.to_pretty_string_with_width(width),
+            Err(_) => String::from("<borrowed>"),
+        }
+    }
+    fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_borrow() {
+            Ok(value) => value.pretty_print(f),
+            Err(_) => f.write_str("<borrowed>"),
+        }
+    }
+    fn to_pretty_string_with_config(&self, config: &PrintConfig) -> String {
+        match self.try_borrow() {
+            Ok(value) => value.to_pretty_string_with_config(config),
+            Err(_) => String::from("<borrowed>"),
+        }
+    }
+    fn pretty_print_with_config(
+        &self,
+        config: &PrintConfig,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        match self.try_borrow() {
+            Ok(value) => value.pretty_print_with_config(config, f),
+            Err(_) => f.write_str("<borrowed>"),
+        }
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for core::num::Wrapping<T> {
+    fn render(&self) -> Document {
+        PrettyPrint::render(&self.0)
+    }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        PrettyPrint::to_pretty_string_with_width(&self.0, width)
+    }
+    fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        PrettyPrint::pretty_print(&self.0, f)
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for core::num::Saturating<T> {
+    fn render(&self) -> Document {
+        PrettyPrint::render(&self.0)
+    }
+    fn to_pretty_string_with_width(&self, width: usize) -> String {
+        PrettyPrint::to_pretty_string_with_width(&self.0, width)
+    }
+    fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        PrettyPrint::pretty_print(&self.0, f)
+    }
+}
+
+/// Ranges are rendered using the usual syntax, e.g. `1..10`, and are always laid out flat
+impl<T: PrettyPrint> PrettyPrint for core::ops::Range<T> {
+    fn render(&self) -> Document {
+        flatten(self.start.render() + ".." + self.end.render())
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for core::ops::RangeInclusive<T> {
+    fn render(&self) -> Document {
+        flatten(self.start().render() + "..=" + self.end().render())
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for core::ops::RangeFrom<T> {
+    fn render(&self) -> Document {
+        flatten(self.start.render() + "..")
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for core::ops::RangeTo<T> {
+    fn render(&self) -> Document {
+        flatten(".." + self.end.render())
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for core::ops::RangeToInclusive<T> {
+    fn render(&self) -> Document {
+        flatten("..=" + self.end.render())
+    }
+}
+
+impl PrettyPrint for core::ops::RangeFull {
+    fn render(&self) -> Document {
+        const_text("..")
+    }
+}
+
+/// [Option] is rendered as `some(<value>)` or `none`.
+///
+/// Like the elements of a [alloc::vec::Vec], the value is placed on its own indented line if it
+/// does not fit following `some(`. Unlike rendering only the value, nested options remain
+/// distinguishable, e.g. `some(none)`.
+impl<T: PrettyPrint> PrettyPrint for Option<T> {
+    fn render(&self) -> Document {
+        match self {
+            Some(value) => "some" + render_collection("(", ")", core::iter::once(value.render())),
+            None => const_text("none"),
+        }
+    }
+}
+
+/// [Result] is rendered as `ok(<value>)` or `err(<error>)`, laid out like `some(<value>)`, see
+/// the impl for [Option].
+impl<T: PrettyPrint, E: PrettyPrint> PrettyPrint for Result<T, E> {
+    fn render(&self) -> Document {
+        match self {
+            Ok(value) => "ok" + render_collection("(", ")", core::iter::once(value.render())),
+            Err(error) => "err" + render_collection("(", ")", core::iter::once(error.render())),
+        }
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for [T] {
+    fn render(&self) -> Document {
+        pretty_list(self)
+    }
+}
+
+impl<T: PrettyPrint, const N: usize> PrettyPrint for [T; N] {
+    fn render(&self) -> Document {
+        self.as_slice().render()
+    }
+}
+
 impl<T: PrettyPrint> PrettyPrint for alloc::vec::Vec<T> {
     fn render(&self) -> Document {
-        let single = self.iter().fold(Document::Empty, |acc, e| match acc {
-            Document::Empty => e.render(),
-            acc => acc + ',' + ' ' + e.render(),
-        });
-        let multi = self.iter().fold(Document::Empty, |acc, e| match acc {
-            Document::Empty => e.render(),
-            acc => acc + ',' + nl() + e.render(),
-        });
-        let single_line = '[' + single + ']';
-        let multi_line = '[' + indent(4, nl() + multi) + nl() + ']';
-        single_line | multi_line
+        self.as_slice().render()
+    }
+}
+
+impl<T: PrettyPrint> PrettyPrint for alloc::collections::VecDeque<T> {
+    fn render(&self) -> Document {
+        pretty_list(self)
     }
 }
 
 impl<T: PrettyPrint> PrettyPrint for alloc::collections::BTreeSet<T> {
     fn render(&self) -> Document {
-        let single = self.iter().fold(Document::Empty, |acc, e| match acc {
-            Document::Empty => e.render(),
-            acc => acc + ',' + ' ' + e.render(),
-        });
-        let multi = self.iter().fold(Document::Empty, |acc, e| match acc {
-            Document::Empty => e.render(),
-            acc => acc + ',' + nl() + e.render(),
-        });
-        let single_line = '{' + single + '}';
-        let multi_line = '{' + indent(4, nl() + multi) + nl() + '}';
-        single_line | multi_line
+        pretty_set(self)
     }
 }
 
 impl<K: PrettyPrint, V: PrettyPrint> PrettyPrint for alloc::collections::BTreeMap<K, V> {
     fn render(&self) -> Document {
-        let single = self.iter().fold(Document::Empty, |acc, (k, v)| match acc {
-            Document::Empty => k.render() + " => " + v.render(),
-            acc => acc + ',' + ' ' + k.render() + " => " + v.render(),
-        });
-        let multi = self.iter().fold(Document::Empty, |acc, (k, v)| match acc {
-            Document::Empty => k.render() + " => " + v.render(),
-            acc => acc + ',' + nl() + k.render() + " => " + v.render(),
-        });
-        let single_line = '{' + single + '}';
-        let multi_line = '{' + indent(4, nl() + multi) + nl() + '}';
-        single_line | multi_line
+        pretty_map(self)
     }
 }
 
-struct Prettier<'a, P: ?Sized + PrettyPrint>(&'a P);
+/// As the iteration order of a [std::collections::HashSet] is unspecified, the elements are
+/// sorted by their text when laid out flat, e.g. `10` sorts before `9`, so that the output is
+/// deterministic
+#[cfg(feature = "std")]
+impl<T: PrettyPrint, S> PrettyPrint for std::collections::HashSet<T, S> {
+    fn render(&self) -> Document {
+        let mut elements = self
+            .iter()
+            .map(|e| {
+                let doc = e.render();
+                (doc.flat_text(), doc)
+            })
+            .collect::<alloc::vec::Vec<_>>();
+        elements.sort_by(|a, b| a.0.cmp(&b.0));
+        render_collection("{", "}", elements.into_iter().map(|(_, doc)| doc))
+    }
+}
 
-impl<'a, P: ?Sized + PrettyPrint> fmt::Display for Prettier<'a, P> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.pretty_print(f)
+/// As the iteration order of a [std::collections::HashMap] is unspecified, the entries are
+/// sorted by the text of their keys, then values, when laid out flat, e.g. `10` sorts before `9`,
+/// so that the output is deterministic
+#[cfg(feature = "std")]
+impl<K: PrettyPrint, V: PrettyPrint, S> PrettyPrint for std::collections::HashMap<K, V, S> {
+    fn render(&self) -> Document {
+        render_map(sorted_entries(self), MapStyle::default())
+    }
+}
+
+/// Paths which are valid UTF-8 are rendered verbatim, as a single unbreakable piece of text, so
+/// that enclosing documents decide where to break lines.
+///
+/// Paths which are not valid UTF-8, or which contain control characters such as line breaks, are
+/// instead rendered quoted, with the offending bytes escaped, e.g. `"foo\xFF.txt"`, so that they
+/// are never silently mangled.
+#[cfg(feature = "std")]
+impl PrettyPrint for std::path::Path {
+    fn render(&self) -> Document {
+        self.as_os_str().render()
+    }
+}
+
+/// Rendered like [std::path::Path]
+#[cfg(feature = "std")]
+impl PrettyPrint for std::path::PathBuf {
+    fn render(&self) -> Document {
+        self.as_os_str().render()
+    }
+}
+
+/// Rendered like [std::path::Path]
+#[cfg(feature = "std")]
+impl PrettyPrint for std::ffi::OsStr {
+    fn render(&self) -> Document {
+        match self.to_str() {
+            Some(s) if !s.contains(char::is_control) => text(s),
+            _ => text(format!("{self:?}")),
+        }
     }
 }
+
+/// Rendered like [std::path::Path]
+#[cfg(feature = "std")]
+impl PrettyPrint for std::ffi::OsString {
+    fn render(&self) -> Document {
+        self.as_os_str().render()
+    }
+}
+
+/// Renders the entries of `map`, sorted by the text of their keys, then values, when laid out flat
+#[cfg(feature = "std")]
+fn sorted_entries<K: PrettyPrint, V: PrettyPrint, S>(
+    map: &std::collections::HashMap<K, V, S>,
+) -> impl Iterator<Item = (Document, Document)> {
+    let mut entries = map
+        .iter()
+        .map(|(k, v)| {
+            let (k, v) = (k.render(), v.render());
+            ((k.flat_text(), v.flat_text()), (k, v))
+        })
+        .collect::<alloc::vec::Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.into_iter().map(|(_, entry)| entry)
+}
+
+/// A wrapper for a map which pretty-prints it using a custom separator between each key and
+/// value, e.g. `{a: 1, b: 2}`, rather than the default of ` => `.
+///
+/// Otherwise, the map is laid out like the [PrettyPrint] impl of the map itself.
+///
+/// ```rust
+/// use std::collections::BTreeMap;
+/// use miden_formatting::prettier::{Map, PrettyPrint};
+///
+/// let map = BTreeMap::from([("a", 1), ("b", 2)]);
+/// assert_eq!(Map::new(&map).with_kv_separator(": ").to_pretty_string(), "{a: 1, b: 2}");
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Map<'a, M: ?Sized> {
+    map: &'a M,
+    style: MapStyle,
+}
+
+impl<'a, M: ?Sized> Map<'a, M> {
+    /// Pretty-print `map`, using ` => ` between each key and value
+    #[inline]
+    pub fn new(map: &'a M) -> Self {
+        Self { map, style: MapStyle::default() }
+    }
+
+    /// Use `separator` between each key and value
+    #[inline]
+    pub fn with_kv_separator(self, separator: &'static str) -> Self {
+        Self {
+            style: MapStyle { kv_sep: separator, ..self.style },
+            ..self
+        }
+    }
+
+    /// Lay out the map using `style`, see [pretty_map_with]
+    #[inline]
+    pub fn with_style(self, style: MapStyle) -> Self {
+        Self { style, ..self }
+    }
+}
+
+impl<'a, K: PrettyPrint, V: PrettyPrint> PrettyPrint
+    for Map<'a, alloc::collections::BTreeMap<K, V>>
+{
+    fn render(&self) -> Document {
+        pretty_map_with(self.map.iter(), self.style)
+    }
+}
+
+/// The entries are sorted like the [PrettyPrint] impl of [std::collections::HashMap]
+#[cfg(feature = "std")]
+impl<'a, K: PrettyPrint, V: PrettyPrint, S> PrettyPrint
+    for Map<'a, std::collections::HashMap<K, V, S>>
+{
+    fn render(&self) -> Document {
+        render_map(sorted_entries(self.map), self.style)
+    }
+}
+
+/// A wrapper for a [alloc::collections::VecDeque] which pretty-prints it with its front and back
+/// annotated, e.g. `front[a, b, c]back`, which makes the state of a queue clearer than the plain
+/// list rendered by the [PrettyPrint] impl of the queue itself.
+///
+/// Otherwise, the queue is laid out like a list, see [pretty_list].
+///
+/// ```rust
+/// use std::collections::VecDeque;
+/// use miden_formatting::prettier::{PrettyPrint, Queue};
+///
+/// let queue = VecDeque::from([1, 2, 3]);
+/// assert_eq!(Queue(&queue).to_pretty_string(), "front[1, 2, 3]back");
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Queue<'a, T>(pub &'a alloc::collections::VecDeque<T>);
+
+impl<'a, T: PrettyPrint> PrettyPrint for Queue<'a, T> {
+    fn render(&self) -> Document {
+        let style = ListStyle {
+            open: "front[",
+            close: "]back",
+            ..ListStyle::default()
+        };
+        pretty_list_with(self.0, style)
+    }
+}
+
+/// Render the items of `iter` as a list, e.g. `[a, b, c]`, using the same layout as [alloc::vec::Vec].
+///
+/// All items are placed on one line if they fit, otherwise each item is placed on its own line.
+/// This avoids collecting an iterator just to pretty-print it.
+pub fn pretty_list<I>(iter: I) -> Document
+where
+    I: IntoIterator,
+    I::Item: PrettyPrint,
+{
+    pretty_list_with(iter, ListStyle::default())
+}
+
+/// The layout of a list rendered by [pretty_list_with].
+///
+/// The default style is the one used by [pretty_list], i.e. `[a, b, c]`, with each item indented
+/// by 4 spaces when broken across lines.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ListStyle {
+    /// The delimiter placed before the first item
+    pub open: &'static str,
+    /// The delimiter placed after the last item
+    pub close: &'static str,
+    /// The number of columns by which items are indented when broken across lines
+    pub indent: u32,
+    /// The separator placed after each item but the last, followed by a space when the items are
+    /// placed on one line, or a line break otherwise
+    pub separator: &'static str,
+    /// Whether to place the separator after the last item when the list is broken across lines
+    pub trailing: bool,
+}
+
+impl Default for ListStyle {
+    fn default() -> Self {
+        Self {
+            open: "[",
+            close: "]",
+            indent: 4,
+            separator: ",",
+            trailing: false,
+        }
+    }
+}
+
+/// Render the items of `iter` as a list, laid out according to `style`.
+///
+/// All items are placed on one line if they fit, e.g. `[a, b, c]`, otherwise each item is placed
+/// on its own line. When broken across lines, the items are indented between the delimiters,
+/// unless both delimiters are empty, in which case the items are not indented. An empty list is
+/// always rendered as just its delimiters.
+///
+/// ```rust
+/// use miden_formatting::prettier::*;
+///
+/// let style = ListStyle { indent: 2, trailing: true, ..ListStyle::default() };
+/// let doc = pretty_list_with(["alpha", "beta"], style);
+/// assert_eq!(format!("{doc}"), "[alpha, beta]");
+/// assert_eq!(format!("{doc:8}"), "[\n  alpha,\n  beta,\n]");
+/// ```
+pub fn pretty_list_with<I>(iter: I, style: ListStyle) -> Document
+where
+    I: IntoIterator,
+    I::Item: PrettyPrint,
+{
+    render_list(iter.into_iter().map(|item| item.render()), style)
+}
+
+/// An adapter which pretty-prints any cloneable iterable as a list, using [pretty_list].
+///
+/// This is useful when a value is needed, rather than a [Document], e.g. as a field of another
+/// type which is rendered via [PrettyPrint]. The iterable is cloned each time it is rendered, so it
+/// should be cheap to clone, e.g. a range, or an iterator over borrowed items.
+///
+/// ```rust
+/// use miden_formatting::prettier::{List, PrettyPrint};
+///
+/// assert_eq!(List(0..3).to_pretty_string(), "[0, 1, 2]");
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct List<I>(pub I);
+
+impl<I> PrettyPrint for List<I>
+where
+    I: IntoIterator + Clone,
+    I::Item: PrettyPrint,
+{
+    fn render(&self) -> Document {
+        pretty_list(self.0.clone())
+    }
+}
+
+/// Render the items of `iter` as a set, e.g. `{a, b, c}`, using the same layout as
+/// [alloc::collections::BTreeSet].
+///
+/// The items are rendered in iteration order, see [pretty_list].
+pub fn pretty_set<I>(iter: I) -> Document
+where
+    I: IntoIterator,
+    I::Item: PrettyPrint,
+{
+    render_collection("{", "}", iter.into_iter().map(|item| item.render()))
+}
+
+/// Render the key/value pairs of `iter` as a map, e.g. `{a => 1, b => 2}`, using the same layout
+/// as [alloc::collections::BTreeMap].
+///
+/// The entries are rendered in iteration order, see [pretty_list].
+pub fn pretty_map<I, K, V>(iter: I) -> Document
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: PrettyPrint,
+    V: PrettyPrint,
+{
+    pretty_map_with(iter, MapStyle::default())
+}
+
+/// The layout of a map rendered by [pretty_map_with].
+///
+/// The default style is the one used by [pretty_map], i.e. `{a => 1, b => 2}`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MapStyle {
+    /// The delimiter placed before the first entry
+    pub open: &'static str,
+    /// The delimiter placed after the last entry
+    pub close: &'static str,
+    /// The separator placed between each key and value
+    pub kv_sep: &'static str,
+    /// Whether to place a comma after the last entry when the map is broken across lines
+    pub trailing: bool,
+}
+
+impl Default for MapStyle {
+    fn default() -> Self {
+        Self {
+            open: "{",
+            close: "}",
+            kv_sep: " => ",
+            trailing: false,
+        }
+    }
+}
+
+/// Render the key/value pairs of `iter` as a map, laid out according to `style`.
+///
+/// All entries are placed on one line if they fit, e.g. `{a: 1, b: 2}`, otherwise each entry is
+/// placed on its own line. When broken across lines, the entries are indented between the
+/// delimiters, unless both delimiters are empty, in which case the entries are not indented, e.g.:
+///
+/// ```text
+/// a = 1,
+/// b = 2
+/// ```
+///
+/// Lines following the first line of a multi-line value are indented relative to its entry.
+pub fn pretty_map_with<I, K, V>(iter: I, style: MapStyle) -> Document
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: PrettyPrint,
+    V: PrettyPrint,
+{
+    render_map(iter.into_iter().map(|(k, v)| (k.render(), v.render())), style)
+}
+
+/// Render the rendered key/value pairs of `entries` as a map laid out according to `style`
+fn render_map(entries: impl Iterator<Item = (Document, Document)>, style: MapStyle) -> Document {
+    let list = ListStyle {
+        open: style.open,
+        close: style.close,
+        trailing: style.trailing,
+        ..ListStyle::default()
+    };
+    render_list(entries.map(|(k, v)| k + style.kv_sep + v), list)
+}
+
+/// Render a struct-like record, e.g. `Name { a: 1, b: 2 }`, from its name and fields.
+///
+/// All fields are placed on one line if they fit, otherwise each field is placed on its own
+/// indented line, followed by a comma:
+///
+/// ```text
+/// Name {
+///     a: 1,
+///     b: 2,
+/// }
+/// ```
+///
+/// A record without fields is rendered as `Name {}`.
+pub fn record(name: &str, fields: &[(&str, Document)]) -> Document {
+    let fields = fields
+        .iter()
+        .map(|(field, value)| text(field) + ": " + value.clone())
+        .collect::<alloc::vec::Vec<_>>();
+    render_fields(text(name) + " {", " ", fields, '}')
+}
+
+/// Render a tuple-like record, e.g. `Name(1, 2)`, from its name and fields.
+///
+/// This is laid out like [record], i.e. when broken across lines, each field is placed on its own
+/// indented line, followed by a comma. A tuple without fields is rendered as `Name()`.
+pub fn tuple(name: &str, fields: &[Document]) -> Document {
+    render_fields(text(name) + '(', "", fields.to_vec(), ')')
+}
+
+/// Render `fields` between `open` and `close`, placing all of them on one line, separated from the
+/// delimiters by `padding`, if they fit, otherwise placing each on its own line.
+fn render_fields(
+    open: Document,
+    padding: &'static str,
+    fields: alloc::vec::Vec<Document>,
+    close: char,
+) -> Document {
+    if fields.is_empty() {
+        return open + close;
+    }
+    let join = |sep: Document| join_shared(&fields, sep);
+    // The fields are flattened so that the record is only placed on one line if all of it fits
+    let single_line = flatten(open.clone() + padding + join(const_text(", ")) + padding + close);
+    let multi_line = open + indent(4, nl() + join(',' + nl()) + ',') + nl() + close;
+    single_line | multi_line
+}
+
+/// Concatenate `docs`, placing `sep` between each of them.
+///
+/// Documents are cheap to clone, so this is used to share the same elements between the layouts
+/// of a choice, rather than rendering them once for each layout.
+fn join_shared(docs: &[Document], sep: Document) -> Document {
+    let mut joined = alloc::vec::Vec::with_capacity(docs.len() * 2);
+    for doc in docs {
+        if !joined.is_empty() {
+            joined.push(sep.clone());
+        }
+        joined.push(doc.clone());
+    }
+    concat_vec(joined)
+}
+
+/// Render a delimited, comma-separated collection of elements, using the default [ListStyle]
+/// with the given delimiters.
+fn render_collection(
+    open: &'static str,
+    close: &'static str,
+    elements: impl Iterator<Item = Document>,
+) -> Document {
+    render_list(elements, ListStyle { open, close, ..ListStyle::default() })
+}
+
+/// Render `elements` as a list laid out according to `style`, see [pretty_list_with].
+///
+/// Each element is rendered exactly once, and shared between both layouts.
+fn render_list(elements: impl Iterator<Item = Document>, style: ListStyle) -> Document {
+    let elements = elements.collect::<alloc::vec::Vec<_>>();
+    if elements.is_empty() {
+        return const_text(style.open) + style.close;
+    }
+    let join = |sep: Document| join_shared(&elements, sep);
+    let single_line =
+        const_text(style.open) + join(style.separator + const_text(" ")) + style.close;
+    let mut multi = join(style.separator + nl());
+    if style.trailing {
+        multi += style.separator;
+    }
+    let multi_line = if style.open.is_empty() && style.close.is_empty() {
+        multi
+    } else {
+        const_text(style.open) + indent(style.indent, nl() + multi) + nl() + style.close
+    };
+    single_line | multi_line
+}
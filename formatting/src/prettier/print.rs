@@ -1,17 +1,952 @@
-use alloc::vec::Vec;
+use alloc::{borrow::Cow, collections::BTreeMap, string::String, vec::Vec};
 use core::fmt::{self, Write};
 
-use super::Document;
+use super::{width, Document, GroupId};
 
+/// Pretty-print `doc` to `f` using a page of `width` columns.
+///
+/// If `f` has a precision set, e.g. `{:80.40}`, it is used as the maximum number of lines to
+/// print, see [PrintConfig::with_max_lines]. If the alternate flag is set, i.e. `{:#}`, the
+/// multi-line layout is always used, see [PrintConfig::with_expand_all].
 pub fn pretty_print(doc: &Document, width: usize, f: &mut fmt::Formatter) -> fmt::Result {
-    let mut printer = PrettyPrinter::new(doc, width);
-    printer.print(f)
+    let config = PrintConfig::new(width)
+        .with_max_lines(f.precision())
+        .with_expand_all(f.alternate());
+    pretty_print_with_config(doc, &config, f)
+}
+
+/// Pretty-print `doc` to `out` using a page of `width` columns.
+///
+/// Unlike [PrettyPrint::pretty_print](super::PrettyPrint::pretty_print), this does not require a
+/// [fmt::Formatter], so it can be used to append to any [Write], e.g. a [String].
+pub fn pretty_print_to<W: ?Sized + Write>(
+    doc: &Document,
+    width: usize,
+    out: &mut W,
+) -> fmt::Result {
+    pretty_print_with_config(doc, &PrintConfig::new(width), out)
+}
+
+pub fn pretty_print_with_config<W: ?Sized + Write>(
+    doc: &Document,
+    config: &PrintConfig,
+    f: &mut W,
+) -> fmt::Result {
+    Printer::from(config).print_doc_with(doc, f, true)
+}
+
+/// Pretty-print `doc` using `config`, passing the output to `sink` as a stream of [RenderEvent].
+///
+/// This allows consuming the output incrementally, e.g. line by line, without first rendering
+/// it to a string. All other means of printing a document are implemented on top of this.
+///
+/// If `sink` returns an error, printing stops immediately, and the error is returned.
+pub fn render_with<S>(doc: &Document, config: &PrintConfig, mut sink: S) -> fmt::Result
+where
+    S: FnMut(RenderEvent<'_>) -> fmt::Result,
+{
+    let mut printer = PrettyPrinter::new(doc, config);
+    printer.print(&mut sink)
+}
+
+/// Pretty-print `doc` like [render_with], additionally reporting each layout decision made by
+/// the printer to `trace`.
+///
+/// This is useful when diagnosing why a document is laid out differently than expected.
+#[cfg(feature = "trace")]
+pub fn render_traced<T, S>(
+    doc: &Document,
+    config: &PrintConfig,
+    mut trace: T,
+    mut sink: S,
+) -> fmt::Result
+where
+    T: FnMut(TraceEvent<'_>),
+    S: FnMut(RenderEvent<'_>) -> fmt::Result,
+{
+    let mut printer = PrettyPrinter::new(doc, config);
+    printer.trace = Some(&mut trace);
+    printer.print(&mut sink)
+}
+
+/// A layout decision made by the pretty printer, as reported by [render_traced].
+#[cfg(feature = "trace")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent<'a> {
+    /// The branches taken at each choice enclosing this one, from the outermost to this one.
+    pub path: &'a [Branch],
+    /// The column at which the choice was made
+    pub col: usize,
+    /// The width remaining on the current line
+    pub remaining: usize,
+    /// The width required by the rest of the line if the left branch is taken.
+    ///
+    /// This is `None` if the choice is in a document being laid out flat, in which case the left
+    /// branch is always taken.
+    pub left_width: Option<usize>,
+    /// The branch which was taken
+    pub branch: Branch,
+}
+
+/// A branch of a choice between two layouts, i.e. `x | y`
+#[cfg(feature = "trace")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Branch {
+    /// The first layout, `x`, usually the single-line layout
+    Left,
+    /// The second layout, `y`, usually the multi-line layout
+    Right,
+}
+
+/// Pretty-print `doc` using `config`, returning the output along with the location of each
+/// region of the output annotated using [super::with_span].
+///
+/// The spans are returned in the order in which they start in the output. Spans which do not
+/// contain any output are omitted. Leading indentation, and trailing whitespace, are not
+/// considered part of a span.
+pub fn render_with_spans(doc: &Document, config: &PrintConfig) -> (String, Vec<SpanEntry>) {
+    let mut output = String::new();
+    let mut spans = Vec::<SpanEntry>::new();
+    let mut open = Vec::<usize>::new();
+    let mut line = 0;
+    let mut col = 0;
+    render_with(doc, config, |event| {
+        match event {
+            RenderEvent::Text(text) => {
+                output.push_str(text);
+                col += measure(text, col, config.tab_width);
+            },
+            RenderEvent::Newline => {
+                output.push('\n');
+                line += 1;
+                col = 0;
+            },
+            RenderEvent::Indent(indent) => {
+                output.extend(core::iter::repeat(' ').take(indent as usize));
+                col += indent as usize;
+            },
+            RenderEvent::SpanStart(id) => {
+                open.push(spans.len());
+                spans.push(SpanEntry {
+                    id,
+                    start_byte: output.len(),
+                    end_byte: output.len(),
+                    start_line: line,
+                    start_col: col,
+                    end_line: line,
+                    end_col: col,
+                });
+            },
+            RenderEvent::SpanEnd(_) => {
+                let span = &mut spans[open.pop().expect("unbalanced span events")];
+                span.end_byte = output.len();
+                span.end_line = line;
+                span.end_col = col;
+            },
+        }
+        Ok(())
+    })
+    .expect("writing to a string is infallible");
+    // Spans are left open if the output was truncated, in which case they end with the output
+    for index in open {
+        let span = &mut spans[index];
+        span.end_byte = output.len();
+        span.end_line = line;
+        span.end_col = col;
+    }
+    (output, spans)
+}
+
+/// Pretty-print `doc` using `config`, returning an error if any line of the output is wider than
+/// the page width.
+///
+/// This can be used to verify that documents which are expected to always fit the page do so,
+/// e.g. in tests. Widths are measured exactly as they are when the printer decides whether or
+/// not a layout fits.
+pub fn render_checked(doc: &Document, config: &PrintConfig) -> Result<String, OverflowReport> {
+    let mut output = String::new();
+    let mut overflows = Vec::<Overflow>::new();
+    let mut line = 0;
+    let mut col = config.initial_indent as usize;
+    let mut check = |line: usize, col: usize| {
+        if col > config.width {
+            overflows.push(Overflow { line, width: col });
+        }
+    };
+    render_with(doc, config, |event| {
+        match event {
+            RenderEvent::Text(text) => {
+                output.push_str(text);
+                col += measure(text, col, config.tab_width);
+            },
+            RenderEvent::Indent(indent) => {
+                output.extend(core::iter::repeat(' ').take(indent as usize));
+                col += indent as usize;
+            },
+            RenderEvent::Newline => {
+                output.push('\n');
+                check(line, col);
+                line += 1;
+                col = 0;
+            },
+            RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => (),
+        }
+        Ok(())
+    })
+    .expect("writing to a string is infallible");
+    check(line, col);
+
+    if overflows.is_empty() {
+        Ok(output)
+    } else {
+        Err(OverflowReport {
+            output,
+            page_width: config.width,
+            overflows,
+        })
+    }
+}
+
+/// The error returned by [render_checked] when the output is wider than the page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverflowReport {
+    /// The rendered output
+    pub output: String,
+    /// The width of the page the output was rendered for
+    pub page_width: usize,
+    /// The lines of the output which are wider than the page, in order
+    pub overflows: Vec<Overflow>,
+}
+
+/// A line of output which is wider than the page, see [OverflowReport]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Overflow {
+    /// The zero-based index of the line
+    pub line: usize,
+    /// The width of the line, in columns
+    pub width: usize,
+}
+
+impl fmt::Display for OverflowReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "output exceeds the page width of {} columns on", self.page_width)?;
+        for (i, overflow) in self.overflows.iter().enumerate() {
+            let sep = if i > 0 { "," } else { "" };
+            write!(f, "{sep} line {} ({} columns)", overflow.line + 1, overflow.width)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for OverflowReport {}
+
+#[cfg(all(feature = "std", not(feature = "core-error")))]
+impl std::error::Error for OverflowReport {}
+
+/// The location of a region of the output annotated using [super::with_span], as returned by
+/// [render_with_spans].
+///
+/// Lines and columns are zero-based, and columns are measured in terms of display width. The end
+/// of a span is exclusive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpanEntry {
+    /// The identifier the region was annotated with
+    pub id: u64,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+/// The output of the pretty printer, as consumed by [render_with].
+///
+/// Text is never emitted with trailing whitespace before a line break, nor does it contain line
+/// breaks itself. The indentation of a line is emitted separately from its content.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderEvent<'a> {
+    /// Some text to be displayed on the current line
+    Text(&'a str),
+    /// A line break
+    Newline,
+    /// Indentation of the current line by `n` spaces
+    Indent(u32),
+    /// The start of a region annotated using [super::with_span]
+    ///
+    /// This is emitted immediately before the first output of the annotated document, and is
+    /// only emitted if there is any such output.
+    SpanStart(u64),
+    /// The end of a region annotated using [super::with_span]
+    SpanEnd(u64),
+}
+
+/// A pretty printer with a fixed configuration, which can be used to print any number of
+/// documents.
+///
+/// This is more convenient than passing the same [PrintConfig] around when printing many
+/// documents with the same settings. The printer either owns its configuration, see
+/// [Printer::new], or borrows it, see the `From<&PrintConfig>` impl, which avoids copying it.
+#[derive(Debug, Clone, Default)]
+pub struct Printer<'c> {
+    config: Cow<'c, PrintConfig>,
+    /// The column at which the cursor was left by the last document printed
+    column: u32,
+    /// The number of line breaks printed so far
+    line: usize,
+    /// Set once a document has been printed, after which documents continue from [Printer::column]
+    resume: bool,
+}
+
+impl Printer<'static> {
+    /// Create a new [Printer] with the given configuration
+    pub fn new(config: PrintConfig) -> Self {
+        Self::with_config(Cow::Owned(config))
+    }
+}
+
+impl<'c> Printer<'c> {
+    fn with_config(config: Cow<'c, PrintConfig>) -> Self {
+        let column = config.initial_indent;
+        Self { config, column, line: 0, resume: false }
+    }
+
+    /// The configuration used by this printer
+    pub fn config(&self) -> &PrintConfig {
+        &self.config
+    }
+
+    /// The column at which the cursor was left by the last document printed using
+    /// [Printer::print_doc]
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+
+    /// The number of line breaks printed so far using [Printer::print_doc]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Set the column at which the next document printed using [Printer::print_doc] starts.
+    ///
+    /// This is useful when other output has been written to the same line since the last
+    /// document was printed.
+    pub fn set_column(&mut self, column: u32) {
+        self.column = column;
+        self.resume = true;
+    }
+
+    /// Pretty-print `doc` to `out`, starting at the column the previous document ended at.
+    ///
+    /// Unlike [Printer::print_to], this tracks the position of the cursor across calls, so that
+    /// any number of documents can be printed to the same stream of lines, with the layout of
+    /// each accounting for the space used by the ones before it. See [Printer::column] and
+    /// [Printer::line].
+    pub fn print_doc<W: ?Sized + Write>(&mut self, doc: &Document, out: &mut W) -> fmt::Result {
+        // Later documents may continue the last line, so whitespace at the end is kept
+        self.print_doc_with(doc, out, false)
+    }
+
+    /// Same as [Printer::print_doc], but if `trim_end` is set, `doc` is the last document printed,
+    /// so whitespace at the end of its output is trailing whitespace, and is discarded
+    fn print_doc_with<W: ?Sized + Write>(
+        &mut self,
+        doc: &Document,
+        out: &mut W,
+        trim_end: bool,
+    ) -> fmt::Result {
+        let mut printer = PrettyPrinter::new(doc, &self.config);
+        if self.resume {
+            printer.resume_at(self.column);
+        }
+        printer.trim_end = trim_end;
+        self.resume = true;
+        let tab_width = self.config.tab_width;
+        let column = &mut self.column;
+        let line = &mut self.line;
+        printer.print(&mut |event| match event {
+            RenderEvent::Text(text) => {
+                *column += measure(text, *column as usize, tab_width) as u32;
+                out.write_str(text)
+            },
+            RenderEvent::Newline => {
+                *column = 0;
+                *line += 1;
+                out.write_char('\n')
+            },
+            RenderEvent::Indent(indent) => {
+                *column += indent;
+                write!(out, "{1:0$}", indent as usize, "")
+            },
+            RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => Ok(()),
+        })
+    }
+
+    /// Pretty-print `doc` to a new [String]
+    pub fn print(&self, doc: &Document) -> String {
+        let mut output = String::new();
+        self.print_to(doc, &mut output).expect("writing to a string is infallible");
+        output
+    }
+
+    /// Pretty-print `doc` to `out`
+    pub fn print_to<W: ?Sized + Write>(&self, doc: &Document, out: &mut W) -> fmt::Result {
+        pretty_print_with_config(doc, &self.config, out)
+    }
+}
+
+impl From<PrintConfig> for Printer<'static> {
+    fn from(config: PrintConfig) -> Self {
+        Self::new(config)
+    }
+}
+
+impl<'c> From<&'c PrintConfig> for Printer<'c> {
+    fn from(config: &'c PrintConfig) -> Self {
+        Self::with_config(Cow::Borrowed(config))
+    }
+}
+
+/// Estimate the number of bytes needed to hold the output of printing `doc`, without rendering it.
+///
+/// This is the size of the single-line layout of `doc`, i.e. the text in the first branch of every
+/// choice, excluding padding, so it is usually exact when the document fits on one line, and an
+/// underestimate otherwise.
+pub fn capacity_hint(doc: &Document) -> usize {
+    let mut bytes = 0;
+    let mut stack = vec![doc];
+    while let Some(doc) = stack.pop() {
+        match doc {
+            Document::Empty | Document::TabStop(_) => (),
+            Document::Newline => bytes += 1,
+            Document::Char(c, _) => bytes += c.len_utf8(),
+            Document::Text(text, _) => bytes += text.len(),
+            Document::SharedText(text, _) => bytes += text.len(),
+            Document::Prefix(text, _, x) => {
+                bytes += text.len();
+                stack.push(x);
+            },
+            Document::Flatten(x)
+            | Document::Indent(_, x)
+            | Document::Group(_, x)
+            | Document::ExpandGroup(_, x)
+            | Document::Annotated(_, x)
+            | Document::Truncate(_, x) => stack.push(x),
+            Document::Concat(x, y) => {
+                stack.push(y);
+                stack.push(x);
+            },
+            Document::Choice(x, _) | Document::IfBreak(_, _, x) => stack.push(x),
+        }
+    }
+    bytes
+}
+
+/// The dimensions of the output produced by printing a [Document], see [Document::measure_at]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Measurement {
+    /// The number of lines in the output.
+    ///
+    /// Empty output has zero lines, otherwise the final line is counted even if it is empty,
+    /// i.e. if the output ends with a line break.
+    pub lines: usize,
+    /// The width of the widest line, in columns
+    pub max_line_width: usize,
+    /// The column at which the output ends, i.e. the width of the last line
+    pub final_column: usize,
+}
+
+/// Compute the dimensions of the output produced by printing `doc` with `config`, without
+/// rendering it.
+pub fn measurement(doc: &Document, config: &PrintConfig) -> Measurement {
+    let mut newlines = 0;
+    let mut written = false;
+    let mut col = 0;
+    let mut max = 0;
+    render_with(doc, config, |event| {
+        match event {
+            RenderEvent::Text(text) => {
+                col += measure(text, col, config.tab_width);
+                max = core::cmp::max(max, col);
+            },
+            RenderEvent::Indent(indent) => col += indent as usize,
+            RenderEvent::Newline => {
+                newlines += 1;
+                col = 0;
+            },
+            RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => (),
+        }
+        written = true;
+        Ok(())
+    })
+    .expect("measuring lines is infallible");
+    Measurement {
+        lines: if written { newlines + 1 } else { 0 },
+        max_line_width: max,
+        final_column: col,
+    }
+}
+
+/// The layout chosen for a group, and why, see [explain]
+#[derive(Debug, Copy, Clone)]
+struct GroupDecision {
+    id: Option<GroupId>,
+    /// The zero-based line and column at which the group starts
+    line: usize,
+    col: usize,
+    layout: GroupLayout,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum GroupLayout {
+    /// The group is flat, as it is in a document which is being laid out flat
+    InsideFlat,
+    /// The group is broken, as it is always broken, see [super::group_or_break]
+    Forced,
+    /// The group is broken, as [PrintConfig::with_expand_all] is set
+    ExpandAll,
+    /// The group is flat, as the line containing it fits in the given number of columns
+    Fits(usize),
+    /// The group is broken, as the line containing it would be the given number of columns wide
+    /// if it were flat
+    Overflows(usize),
+    /// The group is broken, as its flat layout contains a group which is always broken
+    Unflattenable,
+}
+
+/// Print the first `max_lines` lines of `doc` using `config`, see [Document::render_preview].
+///
+/// Returns the rendered lines, along with a flag which is set if the rest of the output was
+/// elided.
+pub fn preview(doc: &Document, config: &PrintConfig, max_lines: usize) -> (String, bool) {
+    let config = config.clone().with_max_lines(Some(max_lines)).with_elision_marker("");
+    let mut printer = PrettyPrinter::new(doc, &config);
+    let mut output = String::new();
+    let mut newlines = 0;
+    printer
+        .print(&mut |event| {
+            match event {
+                RenderEvent::Text(text) => output.push_str(text),
+                RenderEvent::Indent(indent) => {
+                    output.extend(core::iter::repeat(' ').take(indent as usize))
+                },
+                // The line break preceding the elision marker is not part of the preview
+                RenderEvent::Newline if newlines + 1 >= max_lines => (),
+                RenderEvent::Newline => {
+                    newlines += 1;
+                    output.push('\n');
+                },
+                RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => (),
+            }
+            Ok(())
+        })
+        .expect("rendering a preview is infallible");
+    (output, printer.output.elided)
+}
+
+/// Print `doc` using `config`, and describe the layout chosen for each group it contains, see
+/// [Document::explain_at].
+pub fn explain(doc: &Document, config: &PrintConfig) -> String {
+    let mut printer = PrettyPrinter::new(doc, config);
+    printer.groups_explained = Some(Vec::new());
+    printer
+        .print(&mut |_| Ok(()))
+        .expect("explaining layout decisions is infallible");
+
+    let mut report = String::new();
+    for decision in printer.groups_explained.take().unwrap_or_default() {
+        let GroupDecision { id, line, col, layout } = decision;
+        if !report.is_empty() {
+            report.push('\n');
+        }
+        let _ = write!(report, "line {}, column {}: group", line + 1, col + 1);
+        if let Some(id) = id {
+            let _ = write!(report, " {}", id.as_u32());
+        }
+        let width = config.width;
+        let _ = match layout {
+            GroupLayout::InsideFlat => write!(report, " flat, as it is inside a flat layout"),
+            GroupLayout::Forced => write!(report, " broken, as it is always broken"),
+            GroupLayout::ExpandAll => write!(report, " broken, as all groups are broken"),
+            GroupLayout::Fits(end) => write!(
+                report,
+                " flat, as the line is {end} columns wide, within the page width of {width}"
+            ),
+            GroupLayout::Overflows(end) => write!(
+                report,
+                " broken, as the line would be {end} columns wide if flat, exceeding the page \
+                 width of {width}"
+            ),
+            GroupLayout::Unflattenable => {
+                write!(report, " broken, as it contains a group which is always broken")
+            },
+        };
+    }
+    report
+}
+
+/// This type is used to customize the behavior of the pretty printer.
+///
+/// The default configuration uses a width of 80 columns, with no initial indentation.
+#[derive(Debug, Clone)]
+pub struct PrintConfig {
+    width: usize,
+    initial_indent: u32,
+    indent_first_line: bool,
+    split_long_text: bool,
+    tab_width: u32,
+    expand_tabs: bool,
+    expand_all: bool,
+    strict_flatten: bool,
+    max_lines: Option<usize>,
+    max_bytes: Option<usize>,
+    max_blank_lines: Option<usize>,
+    elision_marker: Cow<'static, str>,
+    indent_string: Option<Cow<'static, str>>,
+}
+
+impl Default for PrintConfig {
+    fn default() -> Self {
+        Self::new(80)
+    }
+}
+
+impl PrintConfig {
+    /// Create a new configuration for a page of `width` columns
+    ///
+    /// Any width is valid: on a page of zero width, nothing fits, so the multi-line layout of
+    /// every choice is used. Content which cannot be broken, including indentation which is
+    /// wider than the page, simply overflows it.
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            initial_indent: 0,
+            indent_first_line: false,
+            split_long_text: false,
+            tab_width: 8,
+            expand_tabs: false,
+            expand_all: false,
+            strict_flatten: false,
+            max_lines: None,
+            max_bytes: None,
+            max_blank_lines: None,
+            elision_marker: Cow::Borrowed("…"),
+            indent_string: None,
+        }
+    }
+
+    /// Set the indentation of the context the document is being printed in.
+    ///
+    /// This is useful when the output will be embedded in some other text at a known
+    /// indentation level. The initial indentation is added to the indentation following every
+    /// line break, and the printer assumes the first line starts at column `indent`, so that the
+    /// available width is reduced accordingly.
+    ///
+    /// NOTE: The first line is _not_ indented, unless [PrintConfig::with_indent_first_line] is
+    /// also set, as it is assumed that the caller has already positioned the output there.
+    pub fn with_initial_indent(mut self, indent: u32) -> Self {
+        self.initial_indent = indent;
+        self
+    }
+
+    /// If set, the first line of output is indented by the initial indentation, like all
+    /// subsequent lines.
+    pub fn with_indent_first_line(mut self, indent_first_line: bool) -> Self {
+        self.indent_first_line = indent_first_line;
+        self
+    }
+
+    /// If set, text which does not fit in the remaining space on the current line is split
+    /// across multiple lines, rather than overflowing the page width.
+    ///
+    /// Text is only ever split between characters, and continuation lines are indented at the
+    /// current indentation level. By default, text is never split, as doing so is not safe for
+    /// output which must be parsed again.
+    pub fn with_split_long_text(mut self, split_long_text: bool) -> Self {
+        self.split_long_text = split_long_text;
+        self
+    }
+
+    /// Set the distance between tab stops, in columns.
+    ///
+    /// Tab characters in the output advance the current column to the next tab stop, and are
+    /// measured accordingly when deciding whether or not a layout fits. Defaults to 8.
+    pub fn with_tab_width(mut self, tab_width: u32) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
+    /// If set, tab characters are emitted as the number of spaces between the column they are
+    /// printed at and the next tab stop, see [PrintConfig::with_tab_width].
+    ///
+    /// Tabs are measured the same way regardless, so this does not affect the layout, but the
+    /// output is displayed the same by consumers which do not handle tabs, or which use different
+    /// tab stops. This includes tabs in the indentation, see [PrintConfig::with_indent_string].
+    pub fn with_expand_tabs(mut self, expand_tabs: bool) -> Self {
+        self.expand_tabs = expand_tabs;
+        self
+    }
+
+    /// If set, the multi-line layout of every choice and group is used, regardless of whether or
+    /// not the single-line layout would fit.
+    ///
+    /// Documents which are explicitly [super::flatten]ed are still laid out flat.
+    pub fn with_expand_all(mut self, expand_all: bool) -> Self {
+        self.expand_all = expand_all;
+        self
+    }
+
+    /// If set, the printer panics when it encounters a hard line break in a document which is
+    /// being laid out flat, i.e. one inside a [super::flatten], or a [super::group] which fit on
+    /// one line.
+    ///
+    /// Such line breaks are always honored, see [super::flatten], but this is usually not what
+    /// the author of the document intended, so this can be enabled to find them.
+    pub fn with_strict_flatten(mut self, strict_flatten: bool) -> Self {
+        self.strict_flatten = strict_flatten;
+        self
+    }
+
+    /// Limit the output to at most `max_lines` lines.
+    ///
+    /// If the document would produce more lines than this, printing stops once the limit is
+    /// reached, and the elision marker is emitted on its own line to indicate that the output
+    /// was truncated. See [PrintConfig::with_elision_marker].
+    ///
+    /// A limit of zero lines is valid: the output then consists of just the elision marker,
+    /// unless the document produces no output at all.
+    pub fn with_max_lines(mut self, max_lines: Option<usize>) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Limit the output to at most `max_bytes` bytes, excluding the elision marker.
+    ///
+    /// If the document would produce more output than this, printing stops once the limit is
+    /// reached, and the elision marker is emitted on its own line to indicate that the output
+    /// was truncated. See [PrintConfig::with_elision_marker].
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Collapse each run of consecutive blank lines in the output to at most `max_blank_lines`.
+    ///
+    /// This is useful when the items of a document carry blank lines of their own, e.g. from the
+    /// source they were parsed from, and are then separated by more blank lines. Only lines which
+    /// are entirely empty count as blank, so a line holding just a line prefix is never removed,
+    /// see [super::prefix_lines]. Layout decisions are unaffected, as blank lines have no width.
+    pub fn with_max_blank_lines(mut self, max_blank_lines: Option<usize>) -> Self {
+        self.max_blank_lines = max_blank_lines;
+        self
+    }
+
+    /// Set the text which is emitted when the output is truncated due to exceeding the limits
+    /// set by [PrintConfig::with_max_lines] or [PrintConfig::with_max_bytes].
+    ///
+    /// Defaults to `…`.
+    pub fn with_elision_marker(mut self, marker: impl Into<Cow<'static, str>>) -> Self {
+        self.elision_marker = marker.into();
+        self
+    }
+
+    /// Set the text which is emitted once per level of indentation following a line break.
+    ///
+    /// By default, a document indented by `n` columns using [super::indent] is indented using `n`
+    /// spaces. When an indentation string is set, every [super::indent] instead adds one level of
+    /// indentation, regardless of the amount it specifies, and each level is printed as a copy
+    /// of `indent_string`. The width of a level is the width of `indent_string`, which is taken
+    /// into account when deciding whether or not a layout fits, e.g. indenting using tabs is
+    /// `with_indent_string("\t")`, with each level as wide as a tab stop.
+    ///
+    /// Any other indentation, e.g. [PrintConfig::with_initial_indent], is a number of columns,
+    /// which is printed using as many copies of `indent_string` as fit in it, padded with spaces.
+    pub fn with_indent_string(mut self, indent_string: impl Into<Cow<'static, str>>) -> Self {
+        self.indent_string = Some(indent_string.into());
+        self
+    }
+
+    /// The width of the page, in columns
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The indentation of the context the document is being printed in
+    pub fn initial_indent(&self) -> u32 {
+        self.initial_indent
+    }
 }
 
 struct PrettyPrinter<'a> {
     width: usize,
+    split_long_text: bool,
+    tab_width: u32,
+    expand_all: bool,
+    strict_flatten: bool,
     col: u32,
+    /// The column at which the content of the current line starts, i.e. following any
+    /// indentation and line prefixes
+    line_start: u32,
     chunks: Vec<Chunk<'a>>,
+    /// Whitespace which has been printed, but not yet written to the output.
+    ///
+    /// Trailing whitespace is buffered here until we know whether anything other than a line
+    /// break follows it on the current line. If a line break is emitted next, the buffered
+    /// whitespace is discarded, so that the output never contains lines with trailing whitespace.
+    pending: String,
+    /// Indentation which has been printed, but not yet written to the output.
+    ///
+    /// This is buffered for the same reason as `pending`, but is emitted before it.
+    pending_indent: u32,
+    /// Set when nothing other than whitespace has been printed since the last line break
+    blank_line: bool,
+    /// Set when the end of the document is the end of the output, so whitespace buffered at the
+    /// end is trailing whitespace, see [Printer::print_doc]
+    trim_end: bool,
+    /// The text printed for each level of indentation, and its width, if not spaces
+    indent_string: Option<(Cow<'static, str>, u32)>,
+    /// Records whether each group with an id was broken across lines (`true`) or not (`false`)
+    groups: BTreeMap<GroupId, bool>,
+    /// The line prefixes which have been encountered so far, see [Document::Prefix]
+    prefixes: Vec<LinePrefix<'a>>,
+    /// The annotated documents which are currently being printed, see [Document::Annotated]
+    spans: Vec<OpenSpan>,
+    output: Output,
+    /// The layout decisions made for groups so far, if they are being recorded, see [explain]
+    groups_explained: Option<Vec<GroupDecision>>,
+    /// The callback to which layout decisions are reported, see [render_traced]
+    #[cfg(feature = "trace")]
+    trace: Option<&'a mut dyn FnMut(TraceEvent<'_>)>,
+    /// The choices which have been made so far, as `(parent, branch)`
+    #[cfg(feature = "trace")]
+    choices: Vec<(Option<usize>, Branch)>,
+}
+
+/// Tracks the output emitted by the printer, enforcing any limits on its size
+struct Output {
+    max_lines: Option<usize>,
+    max_bytes: Option<usize>,
+    max_blank_lines: Option<usize>,
+    elision_marker: Cow<'static, str>,
+    /// The number of line breaks emitted so far
+    newlines: usize,
+    /// The number of consecutive blank lines emitted immediately before the current line
+    blank_lines: usize,
+    /// The number of bytes emitted so far
+    bytes: usize,
+    /// Set when nothing has been emitted since the last line break
+    at_line_start: bool,
+    /// Set when the output has been truncated
+    elided: bool,
+    /// The distance between tab stops, if tabs are expanded to spaces
+    expand_tabs: Option<u32>,
+    /// The column the next event is emitted at, only tracked when tabs are expanded
+    column: usize,
+}
+
+impl Output {
+    /// Emit `event` to `sink`, unless doing so would exceed the output limits.
+    ///
+    /// When a limit is reached, as much of `event` as fits is emitted, followed by the elision
+    /// marker, and an error is returned to stop printing. The error is not propagated to the
+    /// caller, as this is not actually a failure.
+    fn emit<S>(&mut self, sink: &mut S, event: RenderEvent<'_>) -> fmt::Result
+    where
+        S: FnMut(RenderEvent<'_>) -> fmt::Result,
+    {
+        if let Some(tab_width) = self.expand_tabs {
+            match event {
+                RenderEvent::Text(text) if text.contains('\t') => {
+                    let expanded = expand_tabs(text, self.column, tab_width);
+                    return self.emit(sink, RenderEvent::Text(&expanded));
+                },
+                RenderEvent::Text(text) => self.column += measure(text, self.column, tab_width),
+                RenderEvent::Indent(indent) => self.column += indent as usize,
+                RenderEvent::Newline => self.column = 0,
+                RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => (),
+            }
+        }
+        if self.max_lines == Some(0) {
+            let empty = match event {
+                RenderEvent::Text(text) => text.is_empty(),
+                RenderEvent::Indent(indent) => indent == 0,
+                RenderEvent::Newline => false,
+                RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => true,
+            };
+            if !empty {
+                return self.elide(sink);
+            }
+        }
+        let remaining = self.max_bytes.map(|max| max.saturating_sub(self.bytes));
+        match event {
+            RenderEvent::Newline => {
+                if self.at_line_start {
+                    // This line break ends a blank line, which may need to be dropped
+                    if self.max_blank_lines.is_some_and(|max| self.blank_lines >= max) {
+                        return Ok(());
+                    }
+                    self.blank_lines += 1;
+                } else {
+                    self.blank_lines = 0;
+                }
+                if self.max_lines.is_some_and(|max| self.newlines + 1 >= max)
+                    || remaining == Some(0)
+                {
+                    return self.elide(sink);
+                }
+                self.newlines += 1;
+                self.bytes += 1;
+                self.at_line_start = true;
+                sink(event)
+            },
+            RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => sink(event),
+            RenderEvent::Text(text) => match remaining {
+                Some(remaining) if text.len() > remaining => {
+                    let mut end = remaining;
+                    while !text.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    if end > 0 {
+                        sink(RenderEvent::Text(&text[..end]))?;
+                        self.at_line_start = false;
+                    }
+                    self.elide(sink)
+                },
+                _ => {
+                    self.bytes += text.len();
+                    self.at_line_start &= text.is_empty();
+                    sink(event)
+                },
+            },
+            RenderEvent::Indent(indent) => match remaining {
+                Some(remaining) if indent as usize > remaining => {
+                    if remaining > 0 {
+                        sink(RenderEvent::Indent(remaining as u32))?;
+                        self.at_line_start = false;
+                    }
+                    self.elide(sink)
+                },
+                _ => {
+                    self.bytes += indent as usize;
+                    self.at_line_start &= indent == 0;
+                    sink(event)
+                },
+            },
+        }
+    }
+
+    fn elide<S>(&mut self, sink: &mut S) -> fmt::Result
+    where
+        S: FnMut(RenderEvent<'_>) -> fmt::Result,
+    {
+        if !self.at_line_start {
+            sink(RenderEvent::Newline)?;
+        }
+        sink(RenderEvent::Text(&self.elision_marker))?;
+        self.elided = true;
+        Err(fmt::Error)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -19,79 +954,649 @@ struct Chunk<'a> {
     doc: &'a Document,
     indent: u32,
     flat: bool,
+    /// The index of the innermost line prefix which applies to this chunk, if any
+    prefix: Option<usize>,
+    /// Set when the choices in this chunk must use their multi-line layout, see
+    /// [Document::ExpandGroup]
+    expand: bool,
+    /// The index of the innermost choice containing this chunk, if any
+    #[cfg(feature = "trace")]
+    choice: Option<usize>,
 }
 
 impl<'a> Chunk<'a> {
     fn with_doc(self, doc: &'a Document) -> Self {
-        Self {
-            doc,
-            indent: self.indent,
-            flat: self.flat,
-        }
+        Self { doc, ..self }
     }
 
     fn indented(self, indent: u32, doc: &'a Document) -> Self {
         Self {
             doc,
             indent: self.indent + indent,
-            flat: self.flat,
+            ..self
         }
     }
 
     fn flat(self, doc: &'a Document) -> Self {
-        Self { doc, indent: self.indent, flat: true }
+        Self { doc, flat: true, ..self }
+    }
+
+    fn expanded(self, expand: bool, doc: &'a Document) -> Self {
+        Self { doc, expand, ..self }
+    }
+
+    fn prefixed(self, prefix: usize, doc: &'a Document) -> Self {
+        Self { doc, prefix: Some(prefix), ..self }
     }
 }
 
+/// An annotated document which is currently being printed
+#[derive(Debug, Clone, Copy)]
+struct OpenSpan {
+    id: u64,
+    /// The number of chunks on the stack when the span was entered.
+    ///
+    /// The span ends once the stack returns to this depth.
+    depth: usize,
+    /// Set once the span has produced some output
+    started: bool,
+}
+
+/// A line prefix which is in effect for some part of the document
+#[derive(Debug, Clone, Copy)]
+struct LinePrefix<'a> {
+    text: &'a str,
+    after_indent: bool,
+    /// The indentation level at which the prefix was introduced
+    indent: u32,
+    /// The index of the enclosing line prefix, if any
+    parent: Option<usize>,
+}
+
 impl<'a> PrettyPrinter<'a> {
-    fn new(doc: &'a Document, width: usize) -> Self {
-        let chunk = Chunk { doc, indent: 0, flat: false };
-        Self { width, col: 0, chunks: vec![chunk] }
+    fn new(doc: &'a Document, config: &PrintConfig) -> Self {
+        let indent = config.initial_indent;
+        let chunk = Chunk {
+            doc,
+            indent,
+            flat: false,
+            prefix: None,
+            expand: false,
+            #[cfg(feature = "trace")]
+            choice: None,
+        };
+        let pending_indent = if config.indent_first_line { indent } else { 0 };
+        Self {
+            width: config.width,
+            split_long_text: config.split_long_text,
+            expand_all: config.expand_all,
+            strict_flatten: config.strict_flatten,
+            tab_width: config.tab_width,
+            col: indent,
+            line_start: indent,
+            chunks: vec![chunk],
+            pending: String::new(),
+            pending_indent,
+            blank_line: true,
+            trim_end: true,
+            indent_string: config.indent_string.clone().map(|text| {
+                let width = measure(&text, 0, config.tab_width) as u32;
+                (text, width)
+            }),
+            groups: BTreeMap::new(),
+            prefixes: Vec::new(),
+            spans: Vec::new(),
+            groups_explained: None,
+            output: Output {
+                max_lines: config.max_lines,
+                max_bytes: config.max_bytes,
+                max_blank_lines: config.max_blank_lines,
+                elision_marker: config.elision_marker.clone(),
+                newlines: 0,
+                blank_lines: 0,
+                bytes: 0,
+                at_line_start: true,
+                elided: false,
+                expand_tabs: config.expand_tabs.then_some(config.tab_width),
+                column: if config.indent_first_line { 0 } else { indent as usize },
+            },
+            #[cfg(feature = "trace")]
+            trace: None,
+            #[cfg(feature = "trace")]
+            choices: Vec::new(),
+        }
     }
 
-    fn print(&mut self, f: &mut fmt::Formatter) -> fmt::Result {
-        while let Some(chunk) = self.chunks.pop() {
+    /// Continue printing on a line on which the cursor is already at `col`
+    fn resume_at(&mut self, col: u32) {
+        self.col = col;
+        self.line_start = col;
+        self.pending_indent = 0;
+        self.blank_line = col == 0;
+        self.output.at_line_start = col == 0;
+        self.output.column = col as usize;
+    }
+
+    fn print<S: FnMut(RenderEvent<'_>) -> fmt::Result>(&mut self, sink: &mut S) -> fmt::Result {
+        match self.print_chunks(sink) {
+            // The output was truncated, which is not an error
+            Err(_) if self.output.elided => Ok(()),
+            result => result,
+        }
+    }
+
+    fn print_chunks<S>(&mut self, sink: &mut S) -> fmt::Result
+    where
+        S: FnMut(RenderEvent<'_>) -> fmt::Result,
+    {
+        loop {
+            self.close_spans(self.chunks.len(), sink)?;
+            let Some(chunk) = self.chunks.pop() else {
+                break;
+            };
             match chunk.doc {
                 Document::Empty => (),
                 Document::Newline | Document::Char('\n', _) => {
-                    f.write_char('\n')?;
-                    // If the next chunk is also a newline, do not apply indentation
-                    let strip_indentation = self
-                        .chunks
-                        .iter()
-                        .rev()
-                        .find(|chunk| !chunk.doc.is_empty())
-                        .map(|chunk| chunk.doc.has_leading_newline())
-                        .unwrap_or(true);
-                    if strip_indentation {
-                        self.col = 0;
-                    } else {
-                        write!(f, "{1:0$}", chunk.indent as usize, "")?;
-                        self.col = chunk.indent;
+                    if self.strict_flatten && chunk.flat {
+                        panic!("encountered a hard line break in a document being laid out flat");
                     }
+                    self.write_newline(chunk, sink)?;
                 },
                 Document::Char(c, width) => {
-                    f.write_char(*c)?;
-                    self.col += width;
-                },
-                Document::Text(text, width) => {
-                    f.write_str(text)?;
-                    self.col += width;
+                    self.write_char(*c, sink)?;
+                    self.col += self.char_width(*c, *width, self.col as usize) as u32;
                 },
+                Document::Text(text, width) => self.write_text(text, *width, chunk, sink)?,
+                Document::SharedText(text, width) => self.write_text(text, *width, chunk, sink)?,
                 Document::Flatten(x) => self.chunks.push(chunk.flat(x)),
-                Document::Indent(i, x) => self.chunks.push(chunk.indented(*i, x)),
+                Document::Indent(i, x) => {
+                    self.chunks.push(chunk.indented(self.indent_width(*i), x))
+                },
                 Document::Concat(x, y) => {
                     self.chunks.push(chunk.with_doc(y));
                     self.chunks.push(chunk.with_doc(x));
                 },
                 Document::Choice(x, y) => {
-                    if chunk.flat || self.fits(chunk.with_doc(x)) {
+                    let first = chunk.flat
+                        || (!chunk.expand && !self.expand_all && self.fits(chunk.with_doc(x)));
+                    #[cfg(feature = "trace")]
+                    let chunk = self.trace_choice(chunk, x, first);
+                    if first {
+                        self.chunks.push(chunk.with_doc(x));
+                    } else {
+                        self.chunks.push(chunk.with_doc(y));
+                    }
+                },
+                Document::Group(id, x) => {
+                    self.explain_group(*id, false, chunk, x);
+                    let broken = !chunk.flat && (self.expand_all || !self.fits(chunk.flat(x)));
+                    if let Some(id) = id {
+                        self.groups.insert(*id, broken);
+                    }
+                    if broken {
+                        self.chunks.push(chunk.expanded(false, x));
+                    } else {
+                        self.chunks.push(chunk.flat(x));
+                    }
+                },
+                Document::ExpandGroup(force_break, x) => {
+                    self.explain_group(None, *force_break, chunk, x);
+                    let broken = !chunk.flat
+                        && (*force_break || self.expand_all || !self.fits(chunk.flat(x)));
+                    if broken {
+                        self.chunks.push(chunk.expanded(true, x));
+                    } else {
+                        self.chunks.push(chunk.flat(x));
+                    }
+                },
+                Document::IfBreak(id, x, y) => {
+                    if self.is_broken(*id, chunk) {
                         self.chunks.push(chunk.with_doc(x));
                     } else {
                         self.chunks.push(chunk.with_doc(y));
                     }
                 },
+                Document::Prefix(text, after_indent, x) => {
+                    // The prefix applies to the line the document starts on as well
+                    let at_line_start = self.col == self.line_start;
+                    self.write_str(text, sink)?;
+                    self.col += self.measure(text, self.col as usize) as u32;
+                    if at_line_start {
+                        self.line_start = self.col;
+                    }
+                    self.prefixes.push(LinePrefix {
+                        text,
+                        after_indent: *after_indent,
+                        indent: chunk.indent,
+                        parent: chunk.prefix,
+                    });
+                    self.chunks.push(chunk.prefixed(self.prefixes.len() - 1, x));
+                },
+                Document::Truncate(max_width, x) => self.write_truncated(*max_width, x, sink)?,
+                Document::TabStop(column) => {
+                    // The padding is whitespace, so it is buffered like any other trailing whitespace
+                    let padding = tabstop_padding(self.col as usize, *column);
+                    self.pending.extend(core::iter::repeat(' ').take(padding));
+                    self.col += padding as u32;
+                },
+                Document::Annotated(id, x) => {
+                    self.spans.push(OpenSpan {
+                        id: *id,
+                        depth: self.chunks.len(),
+                        started: false,
+                    });
+                    self.chunks.push(chunk.with_doc(x));
+                },
+            }
+        }
+        // Whitespace at the end of the output is trailing whitespace on the last line, but if the
+        // output is continued by another document, it is only discarded from a blank line
+        if !self.trim_end && !self.blank_line {
+            self.flush_pending(sink)?;
+        }
+        self.pending.clear();
+        self.pending_indent = 0;
+        Ok(())
+    }
+
+    /// Report the decision made for the choice between `x` and some alternative in `chunk`.
+    ///
+    /// Returns `chunk` updated to record the decision, so that the path to nested choices can
+    /// be reconstructed.
+    #[cfg(feature = "trace")]
+    fn trace_choice(&mut self, chunk: Chunk<'a>, x: &'a Document, first: bool) -> Chunk<'a> {
+        let Some(trace) = self.trace.take() else {
+            return chunk;
+        };
+        let branch = if first { Branch::Left } else { Branch::Right };
+        self.choices.push((chunk.choice, branch));
+        let index = self.choices.len() - 1;
+
+        let mut path = vec![];
+        let mut next = Some(index);
+        while let Some(index) = next {
+            let (parent, branch) = self.choices[index];
+            path.push(branch);
+            next = parent;
+        }
+        path.reverse();
+
+        let col = self.col as usize;
+        let left_width = if chunk.flat {
+            None
+        } else {
+            self.fit_width(chunk.with_doc(x), &self.chunks, col, usize::MAX)
+                .map(|end| end - col)
+        };
+        trace(TraceEvent {
+            path: &path,
+            col,
+            remaining: self.width.saturating_sub(col),
+            left_width,
+            branch,
+        });
+        self.trace = Some(trace);
+
+        Chunk { choice: Some(index), ..chunk }
+    }
+
+    /// End all spans entered when the chunk stack was at least `depth` chunks deep.
+    fn close_spans<S>(&mut self, depth: usize, sink: &mut S) -> fmt::Result
+    where
+        S: FnMut(RenderEvent<'_>) -> fmt::Result,
+    {
+        while let Some(span) = self.spans.last().copied() {
+            if span.depth < depth {
+                break;
+            }
+            self.spans.pop();
+            if span.started {
+                self.output.emit(sink, RenderEvent::SpanEnd(span.id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start all spans which have not yet produced any output, as some is about to be written.
+    fn start_spans<S>(&mut self, sink: &mut S) -> fmt::Result
+    where
+        S: FnMut(RenderEvent<'_>) -> fmt::Result,
+    {
+        let unstarted = self.spans.iter().rev().take_while(|span| !span.started).count();
+        let first = self.spans.len() - unstarted;
+        for span in self.spans[first..].iter_mut() {
+            span.started = true;
+            self.output.emit(sink, RenderEvent::SpanStart(span.id))?;
+        }
+        Ok(())
+    }
+
+    /// Record the layout decision made for the group `x`, if decisions are being recorded.
+    ///
+    /// This mirrors the decision made when printing a [Document::Group] or [Document::ExpandGroup].
+    fn explain_group(
+        &mut self,
+        id: Option<GroupId>,
+        force_break: bool,
+        chunk: Chunk<'a>,
+        x: &'a Document,
+    ) {
+        let Some(mut decisions) = self.groups_explained.take() else {
+            return;
+        };
+        let col = self.col as usize;
+        let layout = if chunk.flat {
+            GroupLayout::InsideFlat
+        } else if force_break {
+            GroupLayout::Forced
+        } else if self.expand_all {
+            GroupLayout::ExpandAll
+        } else {
+            match self.fit_width(chunk.flat(x), &self.chunks, col, usize::MAX) {
+                Some(end) if end <= self.width => GroupLayout::Fits(end),
+                Some(end) => GroupLayout::Overflows(end),
+                None => GroupLayout::Unflattenable,
             }
+        };
+        decisions.push(GroupDecision {
+            id,
+            line: self.output.newlines,
+            col,
+            layout,
+        });
+        self.groups_explained = Some(decisions);
+    }
+
+    /// Returns true if the group referenced by `id` was broken across lines.
+    ///
+    /// If no group is referenced, the layout of `chunk` is used instead.
+    fn is_broken(&self, id: Option<GroupId>, chunk: Chunk<'a>) -> bool {
+        match id {
+            None => !chunk.flat,
+            Some(id) => self.groups.get(&id).copied().unwrap_or(false),
+        }
+    }
+
+    fn write_newline<S: FnMut(RenderEvent<'_>) -> fmt::Result>(
+        &mut self,
+        chunk: Chunk<'a>,
+        sink: &mut S,
+    ) -> fmt::Result {
+        self.pending.clear();
+        self.pending_indent = 0;
+        self.blank_line = true;
+        self.output.emit(sink, RenderEvent::Newline)?;
+        self.col = 0;
+        let Some(prefix) = chunk.prefix else {
+            self.write_indent(chunk.indent);
+            self.line_start = self.col;
+            return Ok(());
+        };
+
+        // Line prefixes are emitted from the outermost to the innermost
+        let mut prefixes = vec![];
+        let mut next = Some(prefix);
+        while let Some(index) = next {
+            let prefix = self.prefixes[index];
+            prefixes.push(prefix);
+            next = prefix.parent;
+        }
+        let mut indent = 0;
+        for prefix in prefixes.iter().rev().filter(|prefix| !prefix.after_indent) {
+            self.write_indent(prefix.indent.saturating_sub(indent));
+            indent = core::cmp::max(indent, prefix.indent);
+            self.write_str(prefix.text, sink)?;
+            self.col += self.measure(prefix.text, self.col as usize) as u32;
+        }
+        self.write_indent(chunk.indent.saturating_sub(indent));
+        for prefix in prefixes.iter().rev().filter(|prefix| prefix.after_indent) {
+            self.write_str(prefix.text, sink)?;
+            self.col += self.measure(prefix.text, self.col as usize) as u32;
+        }
+        self.line_start = self.col;
+        Ok(())
+    }
+
+    /// Indent the current line by `indent` columns.
+    ///
+    /// The indentation is buffered like any other trailing whitespace, so if nothing follows it
+    /// on this line, it is never written to the output.
+    fn write_indent(&mut self, indent: u32) {
+        if self.pending.is_empty() {
+            self.pending_indent += indent;
+        } else {
+            let (levels, spaces) = self.indent_levels(indent);
+            if let Some((text, _)) = self.indent_string.as_ref() {
+                for _ in 0..levels {
+                    self.pending.push_str(text);
+                }
+            }
+            self.pending.extend(core::iter::repeat(' ').take(spaces as usize));
+        }
+        self.col += indent;
+    }
+
+    /// Returns the number of columns the contents of a [Document::Indent] are indented by
+    #[inline]
+    fn indent_width(&self, indent: u32) -> u32 {
+        match self.indent_string {
+            Some((_, width)) => width,
+            None => indent,
+        }
+    }
+
+    /// Splits `indent` columns into the number of levels of indentation which fit in it, and
+    /// the number of spaces remaining, see [PrintConfig::with_indent_string]
+    fn indent_levels(&self, indent: u32) -> (u32, u32) {
+        match self.indent_string {
+            Some((_, width)) if width > 0 => (indent / width, indent % width),
+            _ => (0, indent),
+        }
+    }
+
+    /// Write `doc` on a single line, truncating it to at most `max_width` columns, see
+    /// [Document::Truncate].
+    fn write_truncated<S>(&mut self, max_width: u32, doc: &Document, sink: &mut S) -> fmt::Result
+    where
+        S: FnMut(RenderEvent<'_>) -> fmt::Result,
+    {
+        const ELLIPSIS: char = '…';
+
+        // Render the document by itself, stopping as soon as it is known to overflow
+        let max_width = max_width as usize;
+        let config = PrintConfig::new(usize::MAX).with_tab_width(self.tab_width);
+        let (col, tab_width) = (self.col as usize, self.tab_width);
+        let mut text = String::new();
+        let mut text_width = 0;
+        let mut overflow = false;
+        // NOTE: The callback is type-erased, as this function is reachable from the printer it uses
+        let mut capture = |event: RenderEvent<'_>| {
+            match event {
+                RenderEvent::Text(t) => {
+                    text.push_str(t);
+                    // Tabs extend to the next tab stop of the line the text is printed on
+                    text_width += measure(t, col + text_width, tab_width);
+                },
+                RenderEvent::Indent(n) => {
+                    text.extend(core::iter::repeat(' ').take(n as usize));
+                    text_width += n as usize;
+                },
+                RenderEvent::Newline => overflow = true,
+                RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => (),
+            }
+            overflow |= text_width > max_width;
+            if overflow {
+                Err(fmt::Error)
+            } else {
+                Ok(())
+            }
+        };
+        let mut erased: &mut dyn FnMut(RenderEvent<'_>) -> fmt::Result = &mut capture;
+        let _ = PrettyPrinter::new(doc, &config).print(&mut erased);
+
+        if overflow || self.measure(&text, col) > max_width {
+            // Keep as much of the text as fits, leaving room for the ellipsis
+            let available = max_width.saturating_sub(width::char_width(ELLIPSIS));
+            let mut end_col = col;
+            let mut end = 0;
+            for (offset, unit, width) in width::segments(&text) {
+                end_col += match unit {
+                    "\t" => self.tab_stop_width(end_col),
+                    _ => width,
+                };
+                if end_col - col > available {
+                    break;
+                }
+                end = offset + unit.len();
+            }
+            text.truncate(end);
+            if max_width > 0 {
+                text.push(ELLIPSIS);
+            }
+        }
+        self.write_str(&text, sink)?;
+        self.col += self.measure(&text, col) as u32;
+        Ok(())
+    }
+
+    /// Write `text` of the given precomputed `width`, splitting it across lines if it does not fit
+    /// and long text is to be split, see [PrintConfig::with_split_long_text].
+    fn write_text<S: FnMut(RenderEvent<'_>) -> fmt::Result>(
+        &mut self,
+        text: &'a str,
+        width: u32,
+        chunk: Chunk<'a>,
+        sink: &mut S,
+    ) -> fmt::Result {
+        let text_width = self.text_width(text, width, self.col as usize);
+        if self.split_long_text && self.col as usize + text_width > self.width {
+            return self.write_split_text(text, chunk, sink);
+        }
+        self.write_str(text, sink)?;
+        self.col += text_width as u32;
+        Ok(())
+    }
+
+    /// Write `text`, splitting it across as many lines as needed to fit within the page width.
+    fn write_split_text<S: FnMut(RenderEvent<'_>) -> fmt::Result>(
+        &mut self,
+        text: &str,
+        chunk: Chunk<'a>,
+        sink: &mut S,
+    ) -> fmt::Result {
+        let mut col = self.col as usize;
+        let mut start = 0;
+        let mut split = false;
+        for (offset, unit, width) in width::segments(text) {
+            let width = match unit {
+                "\t" => self.tab_stop_width(col),
+                _ => width,
+            };
+            // Break before `unit` if it would overflow the page, but only if that makes progress,
+            // i.e. something was written on the current line, otherwise we would never terminate
+            let progress = offset > start || (!split && col > self.line_start as usize);
+            if col + width > self.width && progress {
+                self.write_str(&text[start..offset], sink)?;
+                self.write_newline(chunk, sink)?;
+                col = self.col as usize;
+                start = offset;
+                split = true;
+                // Whitespace at the point the text is split is dropped, rather than being
+                // carried over to the start of the next line
+                if unit.chars().all(is_trimmable) {
+                    start += unit.len();
+                    continue;
+                }
+            }
+            col += width;
+        }
+        self.write_str(&text[start..], sink)?;
+        self.col = col as u32;
+        Ok(())
+    }
+
+    fn write_char<S: FnMut(RenderEvent<'_>) -> fmt::Result>(
+        &mut self,
+        c: char,
+        sink: &mut S,
+    ) -> fmt::Result {
+        if is_trimmable(c) {
+            self.pending.push(c);
+            return Ok(());
+        }
+        self.flush_pending(sink)?;
+        self.start_spans(sink)?;
+        self.output.emit(sink, RenderEvent::Text(c.encode_utf8(&mut [0; 4])))
+    }
+
+    fn write_str<S: FnMut(RenderEvent<'_>) -> fmt::Result>(
+        &mut self,
+        text: &str,
+        sink: &mut S,
+    ) -> fmt::Result {
+        let trimmed = text.trim_end_matches(is_trimmable);
+        if !trimmed.is_empty() {
+            self.flush_pending(sink)?;
+            self.start_spans(sink)?;
+            self.output.emit(sink, RenderEvent::Text(trimmed))?;
+        }
+        self.pending.push_str(&text[trimmed.len()..]);
+        Ok(())
+    }
+
+    /// Returns the width of `c` when printed at column `col`, given its precomputed `width`
+    #[inline]
+    fn char_width(&self, c: char, width: u32, col: usize) -> usize {
+        if c == '\t' {
+            self.tab_stop_width(col)
+        } else {
+            width as usize
+        }
+    }
+
+    /// Returns the width of `text` when printed at column `col`, given its precomputed `width`.
+    ///
+    /// The precomputed width is only accurate for text without tabs, as the width of a tab
+    /// depends on the column it is printed at.
+    fn text_width(&self, text: &str, width: u32, col: usize) -> usize {
+        if !text.contains('\t') {
+            return width as usize;
+        }
+        self.measure(text, col)
+    }
+
+    /// Returns the width of `text` when printed at column `col`
+    #[inline]
+    fn measure(&self, text: &str, col: usize) -> usize {
+        measure(text, col, self.tab_width)
+    }
+
+    /// Returns the number of columns between `col` and the next tab stop
+    #[inline]
+    fn tab_stop_width(&self, col: usize) -> usize {
+        tab_stop_width(col, self.tab_width)
+    }
+
+    fn flush_pending<S>(&mut self, sink: &mut S) -> fmt::Result
+    where
+        S: FnMut(RenderEvent<'_>) -> fmt::Result,
+    {
+        self.blank_line = false;
+        if self.pending_indent > 0 {
+            let (levels, spaces) = self.indent_levels(self.pending_indent);
+            if let Some((text, _)) = self.indent_string.as_ref() {
+                for _ in 0..levels {
+                    self.output.emit(sink, RenderEvent::Text(text))?;
+                }
+            }
+            if spaces > 0 {
+                self.output.emit(sink, RenderEvent::Indent(spaces))?;
+            }
+            self.pending_indent = 0;
+        }
+        if !self.pending.is_empty() {
+            self.output.emit(sink, RenderEvent::Text(&self.pending))?;
+            self.pending.clear();
         }
         Ok(())
     }
@@ -102,16 +1607,31 @@ impl<'a> PrettyPrinter<'a> {
     /// This is used in conjunction with `Document::Choice` to determine which layout to actually
     /// display between the two choices: the single-line layout, or the multi-line layout. If the
     /// single-line layout does not fit on the current line, then the multi-line layout is used.
+    ///
+    /// Like the `fits` function described by Wadler, the measurement does not stop at the end of
+    /// the chunk, but continues through the rest of the document, in the layout it will be printed
+    /// in, up to the next line break. Any content which follows the chunk on the same line must
+    /// fit as well, while anything following that line break is irrelevant.
     fn fits(&self, chunk: Chunk<'a>) -> bool {
-        let mut remaining = self.width.saturating_sub(self.col as usize);
+        self.fit_width(chunk, &self.chunks, self.col as usize, self.width).is_some()
+    }
+
+    /// Returns the column at which the current line ends if `chunk`, followed by `chunks`, is
+    /// printed starting at column `col`, or `None` if the line would extend past column `limit`.
+    fn fit_width(
+        &self,
+        chunk: Chunk<'a>,
+        mut chunks: &[Chunk<'a>],
+        mut col: usize,
+        limit: usize,
+    ) -> Option<usize> {
         let mut stack = vec![chunk];
-        let mut chunks = self.chunks.as_slice();
 
         loop {
             let chunk = match stack.pop() {
                 Some(chunk) => chunk,
                 None => match chunks.split_last() {
-                    None => return true,
+                    None => return Some(col),
                     Some((chunk, more_chunks)) => {
                         chunks = more_chunks;
                         *chunk
@@ -120,16 +1640,30 @@ impl<'a> PrettyPrinter<'a> {
             };
 
             match &chunk.doc {
-                Document::Empty | Document::Newline => return true,
-                Document::Char(_, text_width) | Document::Text(_, text_width) => {
-                    if *text_width as usize <= remaining {
-                        remaining -= *text_width as usize;
-                    } else {
-                        return false;
+                // An empty document says nothing about whether the rest of the line fits, e.g. the
+                // flat branch of an `if_break` is often empty, so measurement continues past it
+                Document::Empty => (),
+                Document::Newline | Document::Char('\n', _) => return Some(col),
+                Document::Char(c, width) => {
+                    col += self.char_width(*c, *width, col);
+                    if col > limit {
+                        return None;
+                    }
+                },
+                Document::Text(text, width) => {
+                    col += self.text_width(text, *width, col);
+                    if col > limit {
+                        return None;
+                    }
+                },
+                Document::SharedText(text, width) => {
+                    col += self.text_width(text, *width, col);
+                    if col > limit {
+                        return None;
                     }
                 },
                 Document::Flatten(x) => stack.push(chunk.flat(x)),
-                Document::Indent(i, x) => stack.push(chunk.indented(*i, x)),
+                Document::Indent(i, x) => stack.push(chunk.indented(self.indent_width(*i), x)),
                 Document::Concat(x, y) => {
                     stack.push(chunk.with_doc(y));
                     stack.push(chunk.with_doc(x));
@@ -143,7 +1677,87 @@ impl<'a> PrettyPrinter<'a> {
                         stack.push(chunk.with_doc(y));
                     }
                 },
+                // Nested groups are measured using the layout of the enclosing chunk
+                Document::Group(_, x) => stack.push(chunk.with_doc(x)),
+                // A group which is always broken can never be laid out flat
+                Document::ExpandGroup(true, _) if chunk.flat => return None,
+                Document::ExpandGroup(_, x) => stack.push(chunk.with_doc(x)),
+                Document::Prefix(text, _, x) => {
+                    col += self.measure(text, col);
+                    if col > limit {
+                        return None;
+                    }
+                    stack.push(chunk.with_doc(x));
+                },
+                Document::Annotated(_, x) => stack.push(chunk.with_doc(x)),
+                Document::Truncate(max_width, x) => {
+                    let max_col = col + *max_width as usize;
+                    col = self.fit_width(chunk.flat(x), &[], col, max_col).unwrap_or(max_col);
+                    if col > limit {
+                        return None;
+                    }
+                },
+                Document::IfBreak(id, x, y) => {
+                    if self.is_broken(*id, chunk) {
+                        stack.push(chunk.with_doc(x));
+                    } else {
+                        stack.push(chunk.with_doc(y));
+                    }
+                },
+                Document::TabStop(column) => {
+                    col += tabstop_padding(col, *column);
+                    if col > limit {
+                        return None;
+                    }
+                },
             }
         }
     }
 }
+
+/// Returns the number of spaces printed by a [Document::TabStop] at `column` when at column `col`
+fn tabstop_padding(col: usize, column: u32) -> usize {
+    (column as usize).saturating_sub(col).max(1)
+}
+
+/// Returns the width of `text` when printed at column `col`, with tab stops every `tab_width`
+/// columns.
+fn measure(text: &str, col: usize, tab_width: u32) -> usize {
+    let end = width::segments(text).fold(col, |col, (_, unit, width)| match unit {
+        "\t" => col + tab_stop_width(col, tab_width),
+        _ => col + width,
+    });
+    end - col
+}
+
+/// Returns `text` with each tab replaced by spaces up to the next tab stop, when printed at column
+/// `col`, with tab stops every `tab_width` columns.
+fn expand_tabs(text: &str, col: usize, tab_width: u32) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut col = col;
+    for (_, unit, width) in width::segments(text) {
+        if unit == "\t" {
+            let width = tab_stop_width(col, tab_width);
+            expanded.extend(core::iter::repeat(' ').take(width));
+            col += width;
+        } else {
+            expanded.push_str(unit);
+            col += width;
+        }
+    }
+    expanded
+}
+
+/// Returns the number of columns between `col` and the next tab stop
+#[inline]
+fn tab_stop_width(col: usize, tab_width: u32) -> usize {
+    match tab_width as usize {
+        0 => 0,
+        tab_width => tab_width - (col % tab_width),
+    }
+}
+
+#[inline(always)]
+fn is_trimmable(c: char) -> bool {
+    matches!(c, ' ' | '\t')
+}
@@ -0,0 +1,107 @@
+//! This module implements serialization of [Document] when the `serde` feature is enabled.
+//!
+//! The precomputed widths of text are serialized alongside it, as they cannot always be derived
+//! from the text itself, e.g. escape sequences in text constructed with `ansi_text` are not counted.
+
+use alloc::{borrow::Cow, rc::Rc, string::String};
+
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{width, Document, GroupId};
+
+/// The serialized representation of a [Document]
+#[derive(Serialize)]
+#[serde(rename = "Document")]
+enum DocumentRef<'a> {
+    Empty,
+    Newline,
+    Char(char),
+    Text(&'a str, u32),
+    Flatten(&'a Document),
+    Indent(u32, &'a Document),
+    Concat(&'a Document, &'a Document),
+    Choice(&'a Document, &'a Document),
+    Group(Option<GroupId>, &'a Document),
+    ExpandGroup(bool, &'a Document),
+    IfBreak(Option<GroupId>, &'a Document, &'a Document),
+    Prefix(&'a str, bool, &'a Document),
+    Annotated(u64, &'a Document),
+    Truncate(u32, &'a Document),
+    TabStop(u32),
+}
+
+/// The deserialized representation of a [Document], see [DocumentRef]
+#[derive(Deserialize)]
+#[serde(rename = "Document")]
+enum DocumentRepr {
+    Empty,
+    Newline,
+    Char(char),
+    Text(String, u32),
+    Flatten(Document),
+    Indent(u32, Document),
+    Concat(Document, Document),
+    Choice(Document, Document),
+    Group(Option<GroupId>, Document),
+    ExpandGroup(bool, Document),
+    IfBreak(Option<GroupId>, Document, Document),
+    Prefix(String, bool, Document),
+    Annotated(u64, Document),
+    Truncate(u32, Document),
+    TabStop(u32),
+}
+
+impl Serialize for Document {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            Self::Empty => DocumentRef::Empty,
+            Self::Newline => DocumentRef::Newline,
+            Self::Char(c, _) => DocumentRef::Char(*c),
+            Self::Text(text, width) => DocumentRef::Text(text, *width),
+            // The sharing of text is not preserved
+            Self::SharedText(text, width) => DocumentRef::Text(text, *width),
+            Self::Flatten(doc) => DocumentRef::Flatten(doc),
+            Self::Indent(indent, doc) => DocumentRef::Indent(*indent, doc),
+            Self::Concat(a, b) => DocumentRef::Concat(a, b),
+            Self::Choice(a, b) => DocumentRef::Choice(a, b),
+            Self::Group(id, doc) => DocumentRef::Group(*id, doc),
+            Self::ExpandGroup(force_break, doc) => DocumentRef::ExpandGroup(*force_break, doc),
+            Self::IfBreak(id, broken, flat) => DocumentRef::IfBreak(*id, broken, flat),
+            Self::Prefix(prefix, after_indent, doc) => {
+                DocumentRef::Prefix(prefix, *after_indent, doc)
+            },
+            Self::Annotated(id, doc) => DocumentRef::Annotated(*id, doc),
+            Self::Truncate(max_width, doc) => DocumentRef::Truncate(*max_width, doc),
+            Self::TabStop(column) => DocumentRef::TabStop(*column),
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match DocumentRepr::deserialize(deserializer)? {
+            DocumentRepr::Empty => Self::Empty,
+            DocumentRepr::Newline => Self::Newline,
+            DocumentRepr::Char(c) => Self::Char(c, width::char_width(c) as u32),
+            DocumentRepr::Text(text, width) => Self::Text(Cow::Owned(text), width),
+            DocumentRepr::Flatten(doc) => Self::Flatten(Rc::new(doc)),
+            DocumentRepr::Indent(indent, doc) => Self::Indent(indent, Rc::new(doc)),
+            DocumentRepr::Concat(a, b) => Self::Concat(Rc::new(a), Rc::new(b)),
+            DocumentRepr::Choice(a, b) => Self::Choice(Rc::new(a), Rc::new(b)),
+            DocumentRepr::Group(id, doc) => Self::Group(id, Rc::new(doc)),
+            DocumentRepr::ExpandGroup(force_break, doc) => {
+                Self::ExpandGroup(force_break, Rc::new(doc))
+            },
+            DocumentRepr::IfBreak(id, broken, flat) => {
+                Self::IfBreak(id, Rc::new(broken), Rc::new(flat))
+            },
+            DocumentRepr::Prefix(prefix, after_indent, doc) => {
+                Self::Prefix(Cow::Owned(prefix), after_indent, Rc::new(doc))
+            },
+            DocumentRepr::Annotated(id, doc) => Self::Annotated(id, Rc::new(doc)),
+            DocumentRepr::Truncate(max_width, doc) => Self::Truncate(max_width, Rc::new(doc)),
+            DocumentRepr::TabStop(column) => Self::TabStop(column),
+        })
+    }
+}
@@ -1,4 +1,10 @@
-use alloc::{boxed::Box, rc::Rc, string::ToString, vec::Vec};
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
 
 use pretty_assertions::assert_str_eq;
 
@@ -386,3 +392,2685 @@ fn square_plus_1(a: number, b: number) -> number = {
     let actual = ast.to_pretty_string();
     assert_str_eq!(actual, expected);
 }
+
+fn render_with_config(doc: &Document, config: &PrintConfig) -> String {
+    let mut output = String::new();
+    print::pretty_print_with_config(doc, config, &mut output).unwrap();
+    output
+}
+
+fn assert_no_trailing_whitespace(output: &str) {
+    for (i, line) in output.lines().enumerate() {
+        assert_eq!(
+            line.trim_end(),
+            line,
+            "line {} of the output contains trailing whitespace:\n{output}",
+            i + 1
+        );
+    }
+}
+
+#[test]
+fn trailing_whitespace_is_trimmed() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    for width in [10, 20, 40, 80] {
+        assert_no_trailing_whitespace(&format!("{:width$}", ast));
+    }
+
+    let list = vec![1u32, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+    for width in [5, 80] {
+        assert_no_trailing_whitespace(&format!("{:width$}", Pretty(&list)));
+    }
+
+    let doc = text("a,") + ' ' + nl() + text("b = ") + nl() + const_text("c");
+    let expected = "a,\nb =\nc";
+    assert_str_eq!(doc.to_string(), expected);
+
+    // Including at the end of the output, whether or not the last line has other content
+    assert_str_eq!(text("a ").to_string(), "a");
+    assert_str_eq!((text("a") + '\t').to_string(), "a");
+    assert_str_eq!(text("   ").to_string(), "");
+    assert_str_eq!((text("   ") + nl() + "b").to_string(), "\nb");
+    let config = PrintConfig::new(80).with_initial_indent(4).with_indent_first_line(true);
+    assert_str_eq!(render_with_config(&text("  "), &config), "");
+}
+
+#[test]
+fn blank_line_inside_indent_has_no_indentation() {
+    let doc =
+        const_text("block {") + indent(4, nl() + text("a") + nl() + nl() + text("b")) + nl() + '}';
+    let expected = "block {\n    a\n\n    b\n}";
+    let actual = doc.to_string();
+    assert_str_eq!(actual, expected);
+    assert_no_trailing_whitespace(&actual);
+
+    // Indentation at the end of the output is discarded as well
+    let doc = const_text("block {") + indent(4, nl());
+    assert_str_eq!(doc.to_string(), "block {\n");
+}
+
+#[test]
+fn initial_indent_offsets_all_lines() {
+    let list = vec![10000u32, 20000, 30000, 40000, 50000];
+
+    // The list fits on one line at width 40, but not when starting from column 6
+    let expected = "[10000, 20000, 30000, 40000, 50000]";
+    assert_str_eq!(list.to_pretty_string_with_config(&PrintConfig::new(40)), expected);
+
+    let config = PrintConfig::new(40).with_initial_indent(6);
+    let expected = "\
+[
+          10000,
+          20000,
+          30000,
+          40000,
+          50000
+      ]";
+    assert_str_eq!(list.to_pretty_string_with_config(&config), expected);
+
+    let config = config.with_indent_first_line(true);
+    let expected = "      [
+          10000,
+          20000,
+          30000,
+          40000,
+          50000
+      ]";
+    assert_str_eq!(list.to_pretty_string_with_config(&config), expected);
+}
+
+#[test]
+fn line_count_matches_rendered_output() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let doc = ast.render();
+    // At width 80 the parameters fit on one line, at width 20 they do not
+    for (width, expected) in [(80, 3), (20, 8)] {
+        let rendered = format!("{:width$}", doc);
+        assert_eq!(rendered.matches('\n').count() + 1, expected);
+        assert_eq!(doc.line_count_at(width), expected);
+    }
+
+    assert_eq!(Document::Empty.line_count_at(80), 0);
+    assert_eq!(text("foo").line_count_at(80), 1);
+}
+
+#[test]
+fn group_id_drives_distant_if_break() {
+    let params_id = GroupId::new(0);
+    let signature = |params: &[&'static str]| {
+        let params = params.iter().copied().fold(Document::Empty, |acc, param| match acc {
+            Document::Empty => const_text(param),
+            acc => acc + ',' + if_break(nl(), ' '.into()) + const_text(param),
+        });
+        let params = group_with_id(
+            params_id,
+            '(' + indent(4, if_break(nl(), Document::Empty) + params)
+                + if_break(nl(), Document::Empty)
+                + ')',
+        );
+        // If the parameters were broken across lines, place the return type on its own line
+        let return_ty = if_group_breaks(params_id, nl() + "-> number", " -> number".into());
+        "fn foo" + params + return_ty
+    };
+
+    let doc = signature(&["a: number", "b: number"]);
+    assert_str_eq!(format!("{:80}", doc), "fn foo(a: number, b: number) -> number");
+
+    let expected = "\
+fn foo(
+    a: number,
+    b: number
+)
+-> number";
+    assert_str_eq!(format!("{:20}", doc), expected);
+
+    // An empty document does not end the measurement of the line it is on
+    let empty_then_long = Document::Concat(
+        alloc::rc::Rc::new(Document::Empty),
+        alloc::rc::Rc::new(const_text("0123456789")),
+    );
+    let doc = empty_then_long | const_text("short");
+    assert_str_eq!(format!("{:5}", doc), "short");
+    assert_str_eq!(format!("{:10}", doc), "0123456789");
+
+    // Without an id, `if_break` follows the layout of the innermost enclosing group
+    let doc = group(text("a") + if_break(nl(), ' '.into()) + text("b"));
+    assert_str_eq!(format!("{:80}", doc), "a b");
+    assert_str_eq!(format!("{:2}", doc), "a\nb");
+}
+
+#[test]
+fn long_text_is_split_when_enabled() {
+    let hash = "0123456789abcdef".repeat(19);
+    let hash = &hash[..300];
+    let doc = indent(4, const_text("hash:") + nl() + text(hash)) + nl() + const_text("end");
+
+    // By default, text is never split
+    let actual = format!("{:80}", doc);
+    assert_str_eq!(actual, format!("hash:\n    {hash}\nend"));
+
+    // The usable width is 76 columns, so the hash is split across 4 lines
+    let config = PrintConfig::new(80).with_split_long_text(true);
+    let actual = render_with_config(&doc, &config);
+    let lines = actual.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "hash:");
+    for (i, line) in lines[1..5].iter().enumerate() {
+        let expected = &hash[(i * 76)..core::cmp::min((i + 1) * 76, 300)];
+        assert_eq!(*line, format!("    {expected}"));
+    }
+    assert_eq!(lines[5], "end");
+
+    // Double-width characters are never split
+    let config = PrintConfig::new(5).with_split_long_text(true);
+    let doc = text("日本語です");
+    assert_str_eq!(render_with_config(&doc, &config), "日本\n語で\nす");
+}
+
+/// A type which counts the number of times it has been rendered
+struct RenderCounter<'a> {
+    value: u32,
+    renders: &'a core::cell::Cell<usize>,
+}
+impl PartialEq for RenderCounter<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+impl Eq for RenderCounter<'_> {}
+impl core::hash::Hash for RenderCounter<'_> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+impl PrettyPrint for RenderCounter<'_> {
+    fn render(&self) -> Document {
+        self.renders.set(self.renders.get() + 1);
+        display(self.value)
+    }
+}
+
+#[test]
+fn collection_elements_are_rendered_once() {
+    let renders = core::cell::Cell::new(0);
+    let list = (0..10u32)
+        .map(|value| RenderCounter { value, renders: &renders })
+        .collect::<Vec<_>>();
+
+    let doc = list.render();
+    assert_eq!(renders.get(), 10);
+    assert_str_eq!(format!("{:80}", doc), "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]");
+    assert_str_eq!(
+        format!("{:10}", doc),
+        "[\n    0,\n    1,\n    2,\n    3,\n    4,\n    5,\n    6,\n    7,\n    8,\n    9\n]"
+    );
+
+    let set = alloc::collections::BTreeSet::from([1u8, 2, 3]);
+    assert_str_eq!(set.to_pretty_string(), "{1, 2, 3}");
+    let map = alloc::collections::BTreeMap::from([(1u8, 2u8), (3, 4)]);
+    assert_str_eq!(map.to_pretty_string(), "{1 => 2, 3 => 4}");
+    assert_str_eq!(format!("{:8}", Pretty(&map)), "{\n    1 => 2,\n    3 => 4\n}");
+
+    // Unordered collections are sorted without rendering their elements again
+    #[cfg(feature = "std")]
+    #[allow(clippy::mutable_key_type)] // counters are hashed by their values only
+    {
+        let counters = || (0..10u32).map(|value| RenderCounter { value, renders: &renders });
+        renders.set(0);
+        let set = counters().collect::<std::collections::HashSet<_>>();
+        assert_str_eq!(set.to_pretty_string(), "{0, 1, 2, 3, 4, 5, 6, 7, 8, 9}");
+        assert_eq!(renders.get(), 10);
+
+        renders.set(0);
+        let map = counters().zip(counters()).collect::<std::collections::HashMap<_, _>>();
+        assert_str_eq!(
+            Map::new(&map).with_kv_separator(": ").to_pretty_string(),
+            "{0: 0, 1: 1, 2: 2, 3: 3, 4: 4, 5: 5, 6: 6, 7: 7, 8: 8, 9: 9}"
+        );
+        assert_eq!(renders.get(), 20);
+    }
+}
+
+#[test]
+fn documents_pretty_print_as_themselves() {
+    let doc = const_text("fn") + ' ' + text("main");
+    assert_str_eq!(doc.to_pretty_string(), "fn main");
+
+    let docs = alloc::vec![const_text("a"), display(1) + " + " + display(2), Document::Empty];
+    assert_str_eq!(docs.to_pretty_string(), "[a, 1 + 2, ]");
+
+    let dump = alloc::collections::BTreeMap::from([
+        (String::from("name"), text("main")),
+        (String::from("args"), alloc::vec![const_text("x"), const_text("y")].render()),
+    ]);
+    assert_str_eq!(dump.to_pretty_string(), "{args => [x, y], name => main}");
+    assert_str_eq!(
+        format!("{:8}", Pretty(&dump)),
+        "{\n    args => [\n        x,\n        y\n    ],\n    name => main\n}"
+    );
+}
+
+#[test]
+fn pretty_collections_from_iterators() {
+    let numbers = (0..10u8).collect::<alloc::vec::Vec<_>>();
+    let set = numbers.iter().copied().collect::<alloc::collections::BTreeSet<_>>();
+    let map = numbers
+        .iter()
+        .map(|n| (*n, n * 2))
+        .collect::<alloc::collections::BTreeMap<_, _>>();
+    for width in [8, 80] {
+        let config = PrintConfig::new(width);
+        assert_str_eq!(
+            render_with_config(&pretty_list(numbers.iter()), &config),
+            render_with_config(&numbers.render(), &config)
+        );
+        assert_str_eq!(
+            render_with_config(&pretty_set(set.iter()), &config),
+            render_with_config(&set.render(), &config)
+        );
+        assert_str_eq!(
+            render_with_config(&pretty_map(map.iter()), &config),
+            render_with_config(&map.render(), &config)
+        );
+    }
+
+    let evens = pretty_list(numbers.iter().filter(|n| *n % 2 == 0));
+    assert_str_eq!(format!("{}", evens), "[0, 2, 4, 6, 8]");
+
+    // Empty collections never break, even when they don't fit
+    let config = PrintConfig::new(1);
+    assert_str_eq!(render_with_config(&pretty_list(core::iter::empty::<u8>()), &config), "[]");
+    assert_str_eq!(render_with_config(&pretty_set(core::iter::empty::<u8>()), &config), "{}");
+    assert_str_eq!(render_with_config(&pretty_map(core::iter::empty::<(u8, u8)>()), &config), "{}");
+    assert_str_eq!(alloc::vec::Vec::<u8>::new().to_pretty_string(), "[]");
+}
+
+#[test]
+fn to_pretty_string_with_width_ignores_display() {
+    /// A type whose Display impl is a compact summary, unrelated to its pretty-printed form
+    struct Block(alloc::vec::Vec<u32>);
+
+    impl fmt::Display for Block {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "block({} ops)", self.0.len())
+        }
+    }
+
+    impl PrettyPrint for Block {
+        fn render(&self) -> Document {
+            "block " + self.0.render()
+        }
+    }
+
+    let block = Block(alloc::vec![100, 200, 300]);
+    assert_str_eq!(format!("{:10}", block), "block(3 ops)");
+    assert_str_eq!(block.to_pretty_string(), "block [100, 200, 300]");
+    assert_str_eq!(block.to_pretty_string_with_width(80), block.to_pretty_string());
+    assert_str_eq!(
+        block.to_pretty_string_with_width(10),
+        "block [\n    100,\n    200,\n    300\n]"
+    );
+    assert_str_eq!(
+        PrettyPrint::to_pretty_string_with_width(&&block, 10),
+        block.to_pretty_string_with_width(10)
+    );
+}
+
+#[test]
+fn list_adapter_renders_iterables() {
+    assert_str_eq!(List(0..5).to_pretty_string(), "[0, 1, 2, 3, 4]");
+    assert_str_eq!(
+        List(0..5).to_pretty_string_with_width(8),
+        "[\n    0,\n    1,\n    2,\n    3,\n    4\n]"
+    );
+    assert_str_eq!(List(0..0).to_pretty_string(), "[]");
+
+    let names = ["a", "b"];
+    assert_str_eq!(List(names.iter().map(|name| text(*name))).to_pretty_string(), "[a, b]");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn list_adapter_renders_hash_sets() {
+    let set = std::collections::HashSet::from([1u8, 2, 3]);
+    let rendered = List(set.iter()).to_pretty_string();
+    assert_str_eq!(rendered, pretty_list(set.iter()).to_pretty_string());
+    let mut elements = rendered
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(", ")
+        .collect::<alloc::vec::Vec<_>>();
+    elements.sort();
+    assert_eq!(elements, ["1", "2", "3"]);
+}
+
+#[test]
+fn pretty_adapter_forwards_format_flags() {
+    let items = alloc::vec![1, 2, 3];
+    let dynamic: &dyn PrettyPrint = &items;
+    assert_str_eq!(format!("{}", pretty(dynamic)), "[1, 2, 3]");
+    assert_str_eq!(format!("{:5}", pretty(dynamic)), "[\n    1,\n    2,\n    3\n]");
+    assert_str_eq!(format!("{:#}", pretty(&items)), format!("{:5}", Pretty(&items)));
+    assert_str_eq!(format!("{:80.2}", pretty(&items)), format!("{:80.2}", dynamic));
+    assert_eq!(core::mem::size_of::<Pretty<'_, Vec<u8>>>(), core::mem::size_of::<&Vec<u8>>());
+}
+
+#[test]
+fn max_blank_lines_collapses_runs() {
+    let doc = text("a") + nl() + nl() + nl() + nl() + text("b") + nl() + nl() + text("c");
+    let config = PrintConfig::new(80);
+    assert_str_eq!(render_with_config(&doc, &config), "a\n\n\n\nb\n\nc");
+
+    let one = config.clone().with_max_blank_lines(Some(1));
+    assert_str_eq!(render_with_config(&doc, &one), "a\n\nb\n\nc");
+    let two = config.clone().with_max_blank_lines(Some(2));
+    assert_str_eq!(render_with_config(&doc, &two), "a\n\n\nb\n\nc");
+    let none = config.clone().with_max_blank_lines(Some(0));
+    assert_str_eq!(render_with_config(&doc, &none), "a\nb\nc");
+
+    // Indentation on a blank line is never printed, so the line still counts as blank
+    let indented = indent(4, text("a") + nl() + nl() + nl() + text("b"));
+    assert_str_eq!(render_with_config(&indented, &one), "a\n\n    b");
+
+    // Lines holding a prefix are not blank
+    let commented = prefix_lines("//", text("a") + nl() + nl() + nl() + text("b"));
+    assert_str_eq!(render_with_config(&commented, &none), "//a\n//\n//\n//b");
+}
+
+/// A string interner, used as the context when rendering [Call]
+struct Interner(Vec<&'static str>);
+
+#[derive(Copy, Clone)]
+struct Symbol(usize);
+
+impl PrettyPrintWith<Interner> for Symbol {
+    fn render_with(&self, interner: &Interner) -> Document {
+        const_text(interner.0[self.0])
+    }
+}
+
+/// A call expression whose names are interned
+struct Call {
+    callee: Symbol,
+    args: Vec<Symbol>,
+    result: Option<Symbol>,
+}
+
+impl PrettyPrintWith<Interner> for Call {
+    fn render_with(&self, interner: &Interner) -> Document {
+        let call =
+            "call " + self.callee.render_with(interner) + ' ' + self.args.render_with(interner);
+        match self.result {
+            Some(result) => result.render_with(interner) + " = " + call,
+            None => call,
+        }
+    }
+}
+
+#[test]
+fn contextual_rendering_threads_context() {
+    let interner = Interner(alloc::vec!["print", "x", "y", "tmp"]);
+    let call = Call {
+        callee: Symbol(0),
+        args: alloc::vec![Symbol(1), Symbol(2)],
+        result: Some(Symbol(3)),
+    };
+    assert_str_eq!(call.to_pretty_string_with(&interner), "tmp = call print [x, y]");
+
+    let calls = alloc::collections::BTreeMap::from([("first", Some(call)), ("second", None)]);
+    assert_str_eq!(
+        calls.to_pretty_string_with(&interner),
+        "{first => some(tmp = call print [x, y]), second => none}"
+    );
+
+    struct Adapter<'a>(&'a dyn PrettyPrintWith<Interner>, &'a Interner);
+    impl fmt::Display for Adapter<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.pretty_print_with(self.1, f)
+        }
+    }
+    assert_str_eq!(
+        format!("{:10}", Adapter(&calls, &interner)),
+        "{\n    first => some(\n        tmp = call print [\n            x,\n            y\n        ]\n    ),\n    second => none\n}"
+    );
+
+    // Context-free values can be mixed with contextual ones
+    let mixed: (Vec<Symbol>, Vec<u8>) = (alloc::vec![Symbol(1)], alloc::vec![1, 2]);
+    assert_str_eq!(mixed.0.to_pretty_string_with(&interner), "[x]");
+    assert_str_eq!(mixed.1.to_pretty_string_with(&interner), "[1, 2]");
+    assert_str_eq!(NoContext(&mixed.1).to_pretty_string_with(&interner), "[1, 2]");
+}
+
+#[test]
+fn base_indent_applies_to_every_line() {
+    let items = alloc::vec![1, 2, 3];
+    // The first line starts at the base indentation, so only 12 columns remain
+    assert_str_eq!(
+        items.to_pretty_string_with_base_indent(16, 8),
+        "[\n            1,\n            2,\n            3\n        ]"
+    );
+    assert_str_eq!(items.to_pretty_string_with_base_indent(80, 8), "[1, 2, 3]");
+
+    let mut output = String::from("let x = ");
+    items.pretty_print_with_base_indent(&mut output, 16, 8).unwrap();
+    assert_str_eq!(output, "let x = [\n            1,\n            2,\n            3\n        ]");
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Config {
+    name: &'static str,
+    limits: (u32, Option<u32>),
+    tags: Vec<&'static str>,
+}
+
+crate::pretty_via_debug!(Config);
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Alternate(Config);
+
+crate::pretty_via_debug!(Alternate, alternate);
+
+#[test]
+fn pretty_via_debug_rendering() {
+    let config = || Config {
+        name: "main",
+        limits: (1, None),
+        tags: alloc::vec!["a"],
+    };
+    assert_str_eq!(
+        config().to_pretty_string(),
+        r#"Config { name: "main", limits: (1, None), tags: ["a"] }"#
+    );
+    let alternate = Alternate(config());
+    assert_str_eq!(alternate.to_pretty_string(), format!("{:#?}", alternate));
+
+    // Multi-line output is split on line breaks, so it is indented like any other document
+    let nested = "x = " + indent(4, alternate.render());
+    assert_str_eq!(
+        format!("{}", nested),
+        "x = Alternate(\n        Config {\n            name: \"main\",\n            limits: (\n                1,\n                None,\n            ),\n            tags: [\n                \"a\",\n            ],\n        },\n    )"
+    );
+}
+
+#[test]
+fn record_layouts() {
+    let point = record("Point", &[("x", display(1)), ("y", display(-2))]);
+    assert_str_eq!(format!("{}", point), "Point { x: 1, y: -2 }");
+    assert_str_eq!(format!("{:10}", point), "Point {\n    x: 1,\n    y: -2,\n}");
+
+    let nested = record("Line", &[("from", point.clone()), ("to", point)]);
+    assert_str_eq!(
+        format!("{:32}", nested),
+        "Line {\n    from: Point { x: 1, y: -2 },\n    to: Point { x: 1, y: -2 },\n}"
+    );
+
+    let unit = record("Unit", &[]);
+    assert_str_eq!(format!("{}", unit), "Unit {}");
+    assert_str_eq!(format!("{:1}", unit), "Unit {}");
+
+    let pair = tuple("Pair", &[display(1), text("two")]);
+    assert_str_eq!(format!("{}", pair), "Pair(1, two)");
+    assert_str_eq!(format!("{:8}", pair), "Pair(\n    1,\n    two,\n)");
+    assert_str_eq!(format!("{:1}", tuple("Empty", &[])), "Empty()");
+}
+
+/// A generic newtype which is displayed in brackets
+struct Bracketed<T>(T);
+
+impl<T: fmt::Display> fmt::Display for Bracketed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}>", self.0)
+    }
+}
+
+crate::pretty_via_display!(impl[T: fmt::Display] for Bracketed<T>);
+
+/// A non-Copy type with a lifetime parameter
+struct Labelled<'a, T> {
+    label: &'a str,
+    value: Vec<T>,
+}
+
+impl<'a, T: fmt::Debug> fmt::Display for Labelled<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={:?}", self.label, self.value)
+    }
+}
+
+crate::pretty_via_display!(impl['a, T] for Labelled<'a, T> where T: fmt::Debug);
+
+/// A borrowed name which dereferences to its text
+struct Name<'a>(&'a str);
+
+impl<'a> core::ops::Deref for Name<'a> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0
+    }
+}
+
+crate::pretty_via_to_string!(impl['a] for Name<'a>);
+
+#[test]
+fn pretty_via_macros_support_generics() {
+    assert_str_eq!(Bracketed(1u8).to_pretty_string(), "<1>");
+    assert_str_eq!(Bracketed("x").to_pretty_string(), "<x>");
+
+    let label = String::from("xs");
+    let labelled = Labelled { label: &label, value: alloc::vec![1, 2] };
+    assert_str_eq!(labelled.to_pretty_string(), "xs=[1, 2]");
+
+    assert_str_eq!(Name(&label).to_pretty_string(), "xs");
+}
+
+#[cfg(feature = "derive")]
+mod derive {
+    use alloc::{string::String, vec::Vec};
+
+    use pretty_assertions::assert_str_eq;
+
+    use crate::prettier::{Document, PrettyPrint};
+
+    #[derive(PrettyPrint)]
+    struct Procedure {
+        name: String,
+        #[pretty(rename = "params")]
+        args: Vec<u32>,
+        #[pretty(skip)]
+        #[allow(dead_code)]
+        cache: Vec<u8>,
+        #[pretty(flatten)]
+        body: Document,
+        r#return: Option<Kind<u8>>,
+    }
+
+    #[derive(PrettyPrint)]
+    enum Kind<T> {
+        Unit,
+        Newtype(T),
+        Pair(T, #[pretty(skip)] T),
+        Named { value: T, r#type: &'static str },
+    }
+
+    #[derive(PrettyPrint)]
+    struct Marker;
+
+    #[derive(PrettyPrint)]
+    #[allow(dead_code)]
+    enum Never {}
+
+    #[test]
+    fn derived_struct_layout() {
+        let body = alloc::vec![4, 5, 6].render();
+        let procedure = Procedure {
+            name: String::from("main"),
+            args: alloc::vec![1, 2],
+            cache: alloc::vec![],
+            body,
+            r#return: Some(Kind::Newtype(3)),
+        };
+        assert_str_eq!(
+            procedure.to_pretty_string_with_width(100),
+            "Procedure { name: main, params: [1, 2], body: [4, 5, 6], return: some(Newtype(3)) }"
+        );
+        assert_str_eq!(
+            procedure.to_pretty_string_with_width(16),
+            "Procedure {\n    name: main,\n    params: [\n        1,\n        2\n    ],\n    body: [4, 5, 6],\n    return: some(\n        Newtype(\n            3,\n        )\n    ),\n}"
+        );
+        assert_str_eq!(Marker.to_pretty_string(), "Marker");
+    }
+
+    #[test]
+    fn derived_enum_layout() {
+        assert_str_eq!(Kind::<u8>::Unit.to_pretty_string(), "Unit");
+        assert_str_eq!(Kind::Newtype(1u8).to_pretty_string(), "Newtype(1)");
+        assert_str_eq!(Kind::Pair(1u8, 2).to_pretty_string(), "Pair(1)");
+        let named = Kind::Named {
+            value: alloc::vec![10u32, 20, 30],
+            r#type: "list",
+        };
+        assert_str_eq!(named.to_pretty_string(), "Named { value: [10, 20, 30], type: list }");
+        assert_str_eq!(
+            named.to_pretty_string_with_width(26),
+            "Named {\n    value: [10, 20, 30],\n    type: list,\n}"
+        );
+        assert_str_eq!(
+            named.to_pretty_string_with_width(12),
+            "Named {\n    value: [\n        10,\n        20,\n        30\n    ],\n    type: list,\n}"
+        );
+    }
+}
+
+#[test]
+fn trait_objects_render_in_containers() {
+    struct Custom;
+
+    impl PrettyPrint for Custom {
+        fn render(&self) -> Document {
+            const_text("custom")
+        }
+    }
+
+    let items: Vec<Box<dyn PrettyPrint>> =
+        alloc::vec![Box::new(1u8), Box::new(String::from("two")), Box::new(Custom)];
+    assert_str_eq!(items.to_pretty_string(), "[1, two, custom]");
+    assert_str_eq!(items.to_pretty_string_with_width(8), "[\n    1,\n    two,\n    custom\n]");
+    assert_str_eq!(items[2].to_pretty_string(), "custom");
+    assert_str_eq!(format!("{}", items[0]), "1");
+
+    let shared: Vec<Rc<dyn PrettyPrint>> = alloc::vec![Rc::new(Custom), Rc::new(-1i32)];
+    assert_str_eq!(shared.to_pretty_string(), "[custom, -1]");
+    let sync: alloc::sync::Arc<dyn PrettyPrint + Send + Sync> = alloc::sync::Arc::new(3u8);
+    assert_str_eq!(sync.to_pretty_string(), "3");
+    assert_str_eq!(format!("{}", sync), "3");
+
+    let sendable: Vec<Box<dyn PrettyPrint + Send>> =
+        alloc::vec![Box::new(alloc::vec![1u8, 2]), Box::new(Some('x')), Box::new(Custom)];
+    assert_str_eq!(sendable.to_pretty_string(), "[[1, 2], some(x), custom]");
+    assert_str_eq!(format!("{}", sendable[1]), "some(x)");
+    let threadsafe: Vec<Box<dyn PrettyPrint + Send + Sync>> =
+        alloc::vec![Box::new(true), Box::new("four")];
+    assert_str_eq!(threadsafe.to_pretty_string(), "[true, four]");
+    assert_str_eq!(format!("{:#}", threadsafe[1]), "four");
+    let text: Box<str> = "boxed".into();
+    assert_str_eq!(text.to_pretty_string(), "boxed");
+}
+
+#[test]
+fn map_key_value_separators() {
+    let map = alloc::collections::BTreeMap::from([("a", 1u8), ("b", 2)]);
+    assert_str_eq!(Map::new(&map).to_pretty_string(), map.to_pretty_string());
+    assert_str_eq!(Map::new(&map).to_pretty_string(), "{a => 1, b => 2}");
+    assert_str_eq!(Map::new(&map).with_kv_separator(": ").to_pretty_string(), "{a: 1, b: 2}");
+    assert_str_eq!(
+        Map::new(&map).with_kv_separator(" = ").to_pretty_string_with_width(8),
+        "{\n    a = 1,\n    b = 2\n}"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn hash_map_key_value_separators() {
+    let map = std::collections::HashMap::from([(10u8, "x"), (9, "y")]);
+    assert_str_eq!(Map::new(&map).to_pretty_string(), map.to_pretty_string());
+    assert_str_eq!(Map::new(&map).with_kv_separator(": ").to_pretty_string(), "{10: x, 9: y}");
+}
+
+#[test]
+fn chain_layouts() {
+    let call = |name: &'static str| const_text(name) + "()";
+    let short = chain(text("a"), alloc::vec![call("b"), call("c"), const_text("d")]);
+    assert_str_eq!(format!("{}", short), "a.b().c().d");
+
+    let long = chain(
+        text("builder"),
+        alloc::vec![call("with_width"), call("with_indent"), call("build")],
+    );
+    assert_str_eq!(format!("{}", long), "builder.with_width().with_indent().build()");
+    assert_str_eq!(
+        format!("{:20}", long),
+        "builder\n    .with_width()\n    .with_indent()\n    .build()"
+    );
+
+    // Nested in an indented context, the continuations are indented relative to it
+    let stmt = "let x = " + indent(4, long.clone()) + ';';
+    assert_str_eq!(
+        format!("{:24}", stmt),
+        "let x = builder\n        .with_width()\n        .with_indent()\n        .build();"
+    );
+
+    assert_str_eq!(format!("{}", chain(text("a"), alloc::vec![])), "a");
+}
+
+#[test]
+fn format_doc_matches_text() {
+    let name = "main";
+    let arity = 2;
+    assert_eq!(crate::doc_format!("fn {name}/{arity}"), text(format!("fn {name}/{arity}")));
+    assert_eq!(format_doc(format_args!("static")), text("static"));
+    assert_eq!(Document::from(format_args!("{}", 'x')), text('x'));
+    assert_eq!(crate::doc_format!(""), Document::Empty);
+    assert!(matches!(
+        format_doc(format_args!("borrowed")),
+        Document::Text(Cow::Borrowed("borrowed"), 8)
+    ));
+
+    // Line breaks are normalized, as if by `split`
+    let multi = crate::doc_format!("{name}:\n{}\r\n", arity);
+    assert_eq!(multi, split(format!("{name}:\n{arity}\r\n")));
+    assert_str_eq!(format!("{}", indent(4, "x = " + multi)), "x = main:\n    2\n");
+    assert_eq!(format_doc(format_args!("a\nb")), split("a\nb"));
+}
+
+#[test]
+fn group_fit_scans_to_next_hard_break() {
+    let args = group_or_break(
+        const_text("f(") + indent(4, line_or("") + text("alpha, beta")) + line_or("") + ')',
+        false,
+    );
+    let config = PrintConfig::new(16);
+
+    // The group is 14 columns wide, so it fits as long as everything up to the next line break
+    // does too, regardless of what follows that line break
+    let long_line = text("a line which is much longer than the page");
+    let doc = args.clone() + ";" + nl() + long_line.clone();
+    assert_str_eq!(
+        render_with_config(&doc, &config),
+        "f(alpha, beta);\na line which is much longer than the page"
+    );
+
+    // Content following the group on the same line counts towards its width
+    let doc = args.clone() + " + more" + nl() + long_line;
+    assert_str_eq!(
+        render_with_config(&doc, &config),
+        "f(\n    alpha, beta\n) + more\na line which is much longer than the page"
+    );
+
+    // A choice following the group is measured using its multi-line layout, whose first line is
+    // the shortest, so the group stays flat, and the choice breaks instead
+    let doc = args + (const_text(" else_branch") | (nl() + "e")) + ";";
+    assert_str_eq!(render_with_config(&doc, &config), "f(alpha, beta)\ne;");
+}
+
+#[test]
+fn map_styles() {
+    let map = alloc::collections::BTreeMap::from([("a", alloc::vec![1, 2]), ("b", alloc::vec![3])]);
+    let toml = MapStyle {
+        open: "",
+        close: "",
+        kv_sep: " = ",
+        trailing: false,
+    };
+    let json = MapStyle {
+        kv_sep: ": ",
+        trailing: true,
+        ..MapStyle::default()
+    };
+
+    assert_str_eq!(
+        format!("{}", pretty_map_with(map.iter(), MapStyle::default())),
+        "{a => [1, 2], b => [3]}"
+    );
+    assert_str_eq!(format!("{}", pretty_map_with(map.iter(), toml)), "a = [1, 2], b = [3]");
+    assert_str_eq!(format!("{}", pretty_map_with(map.iter(), json)), "{a: [1, 2], b: [3]}");
+
+    // Multi-line values are indented relative to their entry
+    let config = PrintConfig::new(4);
+    assert_str_eq!(
+        render_with_config(&pretty_map_with(map.iter(), MapStyle::default()), &config),
+        "{\n    a => [\n        1,\n        2\n    ],\n    b => [\n        3\n    ]\n}"
+    );
+    assert_str_eq!(
+        render_with_config(&pretty_map_with(map.iter(), toml), &config),
+        "a = [\n    1,\n    2\n],\nb = [\n    3\n]"
+    );
+    assert_str_eq!(
+        render_with_config(&pretty_map_with(map.iter(), json), &config),
+        "{\n    a: [\n        1,\n        2\n    ],\n    b: [\n        3\n    ],\n}"
+    );
+
+    assert_str_eq!(
+        render_with_config(&map.render(), &config),
+        render_with_config(&pretty_map_with(map.iter(), MapStyle::default()), &config)
+    );
+    assert_str_eq!(Map::new(&map).with_style(toml).to_pretty_string(), "a = [1, 2], b = [3]");
+    assert_str_eq!(format!("{}", pretty_map_with(core::iter::empty::<(u8, u8)>(), toml)), "");
+}
+
+#[test]
+fn char_count_flat_differs_from_width() {
+    assert_eq!(Document::Empty.char_count_flat(), 0);
+    assert_eq!(const_text("abc").char_count_flat(), 3);
+
+    // Wide characters occupy two columns, but are a single char
+    let wide = text("日本語");
+    assert_eq!(wide.char_count_flat(), 3);
+    assert_eq!(wide.min_flat_width(), Some(6));
+
+    // Combining characters occupy no columns of their own, but are a separate char
+    let combining = text("e\u{301}") + 'a' + '\u{308}';
+    assert_eq!(combining.char_count_flat(), 4);
+    assert_eq!(combining.min_flat_width(), Some(2));
+
+    // Line breaks count as a single char, while indentation and prefixes are not counted, and
+    // choices are counted using their single-line layout
+    let doc = const_text("ä {") + indent(4, nl() + "ö") + nl() + "}";
+    assert_eq!(doc.char_count_flat(), 7);
+    assert_eq!(prefix_lines("// ", doc).char_count_flat(), 7);
+    assert_eq!(vec!["α", "β"].render().char_count_flat(), 6);
+    assert_eq!(if_break(nl(), const_text("→ ")).char_count_flat(), 2);
+
+    // Counting does not recurse, so it handles very long documents
+    let pieces = (0..100_000).map(|_| Document::from('ß')).collect::<Vec<_>>();
+    assert_eq!(concat_all(&pieces).char_count_flat(), 100_000);
+}
+
+#[test]
+fn list_styles() {
+    let items = ["alpha", "beta"];
+    let config = PrintConfig::new(8);
+    let render = |style: ListStyle| {
+        let doc = pretty_list_with(items, style);
+        (format!("{}", doc), render_with_config(&doc, &config))
+    };
+
+    // The default style is the existing layout of slices and sets
+    let default = render(ListStyle::default());
+    assert_eq!(default, ("[alpha, beta]".into(), "[\n    alpha,\n    beta\n]".into()));
+    assert_str_eq!(render_with_config(&items.render(), &config), default.1);
+    let set = alloc::collections::BTreeSet::from(items);
+    assert_str_eq!(render_with_config(&set.render(), &config), "{\n    alpha,\n    beta\n}");
+
+    assert_eq!(
+        render(ListStyle { indent: 2, ..ListStyle::default() }),
+        ("[alpha, beta]".into(), "[\n  alpha,\n  beta\n]".into())
+    );
+    assert_eq!(
+        render(ListStyle {
+            open: "(",
+            close: ")",
+            ..ListStyle::default()
+        }),
+        ("(alpha, beta)".into(), "(\n    alpha,\n    beta\n)".into())
+    );
+    assert_eq!(
+        render(ListStyle { separator: " |", ..ListStyle::default() }),
+        ("[alpha | beta]".into(), "[\n    alpha |\n    beta\n]".into())
+    );
+    // The trailing separator is only placed when broken across lines
+    assert_eq!(
+        render(ListStyle { trailing: true, ..ListStyle::default() }),
+        ("[alpha, beta]".into(), "[\n    alpha,\n    beta,\n]".into())
+    );
+    // Without delimiters, broken items are not indented
+    assert_eq!(
+        render(ListStyle {
+            open: "",
+            close: "",
+            ..ListStyle::default()
+        }),
+        ("alpha, beta".into(), "alpha,\nbeta".into())
+    );
+
+    let empty = ListStyle {
+        open: "<",
+        close: ">",
+        ..ListStyle::default()
+    };
+    assert_str_eq!(format!("{}", pretty_list_with(core::iter::empty::<u8>(), empty)), "<>");
+}
+
+#[test]
+fn queue_rendering() {
+    let mut queue = alloc::collections::VecDeque::from([2u32, 3]);
+    queue.push_front(1);
+    queue.push_back(4);
+    assert_str_eq!(queue.to_pretty_string(), "[1, 2, 3, 4]");
+    assert_str_eq!(Queue(&queue).to_pretty_string(), "front[1, 2, 3, 4]back");
+    assert_str_eq!(
+        Queue(&queue).to_pretty_string_with_width(8),
+        "front[\n    1,\n    2,\n    3,\n    4\n]back"
+    );
+
+    queue.clear();
+    assert_str_eq!(Queue(&queue).to_pretty_string(), "front[]back");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn path_rendering() {
+    use std::{
+        ffi::{OsStr, OsString},
+        path::{Path, PathBuf},
+    };
+
+    let path = Path::new("/usr/local/lib/libmiden.so");
+    assert_str_eq!(path.to_pretty_string(), "/usr/local/lib/libmiden.so");
+    assert_str_eq!(PathBuf::from(path).to_pretty_string(), "/usr/local/lib/libmiden.so");
+    assert_str_eq!(OsStr::new("日本.txt").to_pretty_string(), "日本.txt");
+    assert_str_eq!(OsString::from("a b").to_pretty_string(), "a b");
+
+    // Paths are never broken across lines, even when they do not fit
+    assert_str_eq!(path.to_pretty_string_with_width(8), "/usr/local/lib/libmiden.so");
+    let paths = alloc::vec![Path::new("/a/long/path"), Path::new("/b")];
+    assert_str_eq!(paths.to_pretty_string_with_width(10), "[\n    /a/long/path,\n    /b\n]");
+
+    // Control characters are escaped, rather than breaking the line
+    assert_str_eq!(Path::new("a\nb").to_pretty_string(), "\"a\\nb\"");
+}
+
+#[cfg(all(feature = "std", unix))]
+#[test]
+fn non_utf8_path_rendering() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::Path};
+
+    let invalid = OsStr::from_bytes(b"caf\xE9/\xFF.txt");
+    assert_str_eq!(invalid.to_pretty_string(), "\"caf\\xE9/\\xFF.txt\"");
+    assert_str_eq!(Path::new(invalid).to_pretty_string(), "\"caf\\xE9/\\xFF.txt\"");
+    assert_str_eq!(invalid.to_os_string().to_pretty_string(), "\"caf\\xE9/\\xFF.txt\"");
+}
+
+#[test]
+fn blank_lines_are_hard_breaks() {
+    let doc = const_text("fn foo() {") + indent(4, nl() + "a" + blank_lines(2) + "b") + nl() + "}";
+    assert_str_eq!(format!("{}", doc), "fn foo() {\n    a\n\n    b\n}");
+    assert_eq!(format!("{}", blank_lines(2)).matches('\n').count(), 2);
+
+    // Like nl(), blank lines have no width, and force enclosing groups to break
+    let para = const_text("one") + blank_lines(3) + "two";
+    assert_eq!(para.trailing_width(), 3);
+    assert!(para.contains_hardline());
+    assert_str_eq!(format!("{}", group(para)), "one\n\n\ntwo");
+
+    assert_eq!(blank_lines(1), nl());
+    assert!(blank_lines(0).is_empty());
+}
+
+#[test]
+fn pretty_cell_memoizes_rendering() {
+    use core::cell::Cell;
+
+    struct Counted<'a> {
+        renders: &'a Cell<usize>,
+        items: alloc::vec::Vec<u32>,
+    }
+    impl PrettyPrint for Counted<'_> {
+        fn render(&self) -> Document {
+            self.renders.set(self.renders.get() + 1);
+            self.items.render()
+        }
+    }
+
+    let renders = Cell::new(0);
+    let mut cell = PrettyCell::new(Counted {
+        renders: &renders,
+        items: alloc::vec![1, 2, 3],
+    });
+    assert_eq!(renders.get(), 0);
+
+    assert_str_eq!(format!("{}", cell), "[1, 2, 3]");
+    assert_str_eq!(format!("{:5}", cell), "[\n    1,\n    2,\n    3\n]");
+    assert_str_eq!(cell.to_pretty_string(), "[1, 2, 3]");
+    assert_eq!(cell.render(), cell.get().items.render());
+    assert_eq!(renders.get(), 1);
+
+    // Modifying the value discards the cached document
+    cell.get_mut().items.push(4);
+    assert_str_eq!(format!("{}", cell), "[1, 2, 3, 4]");
+    assert_str_eq!(format!("{}", cell), "[1, 2, 3, 4]");
+    assert_eq!(renders.get(), 2);
+
+    cell.invalidate();
+    assert_str_eq!(format!("{}", cell), "[1, 2, 3, 4]");
+    assert_eq!(renders.get(), 3);
+    assert_eq!(cell.into_inner().items.len(), 4);
+}
+
+#[test]
+fn try_pretty_print_propagates_errors() {
+    use alloc::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct UnknownOp(u32);
+
+    /// An operation which is resolved by name when rendered
+    struct Op(u32);
+    impl TryPrettyPrint for Op {
+        type Error = UnknownOp;
+
+        fn try_render(&self) -> Result<Document, Self::Error> {
+            match self.0 {
+                0 => Ok(const_text("add")),
+                1 => Ok(const_text("mul")),
+                id => Err(UnknownOp(id)),
+            }
+        }
+    }
+
+    struct Block(Vec<Op>);
+    impl TryPrettyPrint for Block {
+        type Error = UnknownOp;
+
+        fn try_render(&self) -> Result<Document, Self::Error> {
+            try_pretty_list(&self.0)
+        }
+    }
+
+    struct Label(&'static str);
+    impl TryPrettyPrint for Label {
+        type Error = UnknownOp;
+
+        fn try_render(&self) -> Result<Document, Self::Error> {
+            Ok(text(self.0))
+        }
+    }
+
+    struct Function(BTreeMap<u8, (Label, Block)>);
+    impl TryPrettyPrint for Function {
+        type Error = UnknownOp;
+
+        fn try_render(&self) -> Result<Document, Self::Error> {
+            let blocks = self.0.values().map(|(label, block)| (label, block));
+            try_pretty_map_with(blocks, MapStyle { kv_sep: ": ", ..MapStyle::default() })
+        }
+    }
+
+    let function = |ops: Vec<u32>| {
+        Function(BTreeMap::from([
+            (0, (Label("entry"), Block(alloc::vec![Op(0), Op(1)]))),
+            (1, (Label("exit"), Block(ops.into_iter().map(Op).collect()))),
+        ]))
+    };
+
+    let module = alloc::vec![function(alloc::vec![1]), function(alloc::vec![0, 0])];
+    let doc = try_pretty_list(&module).unwrap();
+    assert_str_eq!(
+        doc.to_pretty_string(),
+        "[{entry: [add, mul], exit: [mul]}, {entry: [add, mul], exit: [add, add]}]"
+    );
+    assert_str_eq!(module[0].try_to_pretty_string().unwrap(), "{entry: [add, mul], exit: [mul]}");
+
+    // The first error is propagated out of the nested operation
+    let module = alloc::vec![function(alloc::vec![0]), function(alloc::vec![1, 7, 8])];
+    assert_eq!(try_pretty_list(&module).unwrap_err(), UnknownOp(7));
+    assert_eq!(try_pretty_set(&module[1..]).unwrap_err(), UnknownOp(7));
+    assert_eq!(module[1].try_to_pretty_string().unwrap_err(), UnknownOp(7));
+
+    // Infallible values can be rendered through the same helpers
+    let numbers = alloc::vec![1u8, 2];
+    assert_eq!(try_pretty_list(&numbers), Ok(numbers.render()));
+    assert_eq!(try_pretty_set(&numbers), Ok(pretty_set(&numbers)));
+    let map = BTreeMap::from([(1u8, 2u8)]);
+    assert_eq!(try_pretty_map(&map), Ok(map.render()));
+    assert_eq!(numbers.try_to_pretty_string(), Ok(String::from("[1, 2]")));
+
+    struct Checked<'a>(&'a [Op]);
+    impl fmt::Display for Checked<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match Block(self.0.iter().map(|op| Op(op.0)).collect()).try_pretty_print(f) {
+                Ok(result) => result,
+                Err(UnknownOp(id)) => write!(f, "<unknown op {id}>"),
+            }
+        }
+    }
+    assert_str_eq!(format!("{:5}", Checked(&[Op(0), Op(1)])), "[\n    add,\n    mul\n]");
+    assert_str_eq!(format!("{}", Checked(&[Op(0), Op(3)])), "<unknown op 3>");
+}
+
+#[test]
+fn wrap_text_fills_lines() {
+    let sentence = "The quick brown fox jumps over the lazy dog, then naps.";
+    let config = PrintConfig::new(20);
+    let wrapped = render_with_config(&wrap_text(sentence), &config);
+    assert_str_eq!(wrapped, "The quick brown fox\njumps over the lazy\ndog, then naps.");
+    assert!(wrapped.lines().all(|line| line.len() <= 20));
+    assert_str_eq!(format!("{}", wrap_text(sentence)), sentence);
+
+    // The text is re-wrapped to fit the width available where it is placed
+    let doc = const_text("desc:") + indent(4, nl() + wrap_text(sentence));
+    assert_str_eq!(
+        render_with_config(&doc, &config),
+        "desc:\n    The quick brown\n    fox jumps over\n    the lazy dog,\n    then naps."
+    );
+
+    // Whitespace is normalized, and long words are never broken
+    let messy = "  spaced\tout \n\n text  with_an_unbreakable_long_word ";
+    assert_str_eq!(
+        render_with_config(&wrap_text(messy), &config),
+        "spaced out text\nwith_an_unbreakable_long_word"
+    );
+    assert!(wrap_text(" \n ").is_empty());
+}
+
+#[test]
+fn display_multiline_indents_each_line() {
+    struct Backtrace;
+    impl fmt::Display for Backtrace {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "0: main\n1: start\r\n2: _entry")
+        }
+    }
+
+    let doc = const_text("error: failed") + indent(4, nl() + display_multiline(&Backtrace));
+    assert_str_eq!(format!("{}", doc), "error: failed\n    0: main\n    1: start\n    2: _entry");
+    assert_str_eq!(
+        format!("{}", display_multiline(&Backtrace)),
+        format!("{}", split("0: main\n1: start\r\n2: _entry"))
+    );
+
+    // Blank lines are preserved, and single lines are left as-is
+    assert_str_eq!(
+        format!("{}", indent(2, nl() + display_multiline(&"a\n\nb\n"))),
+        "\n  a\n\n  b\n"
+    );
+    assert_eq!(display_multiline(&42), text("42"));
+    assert!(display_multiline(&"").is_empty());
+
+    // Every line is a slice of the same buffer
+    fn shared_lines(doc: &Document, lines: &mut Vec<*const u8>) {
+        match doc {
+            Document::SharedText(text, _) => lines.push(text.as_ptr()),
+            Document::Concat(x, y) => {
+                shared_lines(x, lines);
+                shared_lines(y, lines);
+            },
+            _ => (),
+        }
+    }
+    let mut lines = Vec::new();
+    shared_lines(&display_multiline(&"abc\r\ndef\nghi"), &mut lines);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1], lines[0].wrapping_add(5));
+    assert_eq!(lines[2], lines[1].wrapping_add(4));
+}
+
+#[test]
+fn key_value_layouts() {
+    let inline = key_value("width", 80u32.render());
+    assert_str_eq!(format!("{}", inline), "width = 80");
+    assert_str_eq!(format!("{:10}", inline), "width = 80");
+
+    // A value which does not fit after the key is placed on the next line, and only broken
+    // itself if it does not fit there either
+    let items = vec!["alpha", "beta", "gamma"].render();
+    let doc = key_value(text("items"), items.clone());
+    assert_str_eq!(format!("{}", doc), "items = [alpha, beta, gamma]");
+    assert_str_eq!(format!("{:26}", doc), "items =\n    [alpha, beta, gamma]");
+    assert_str_eq!(
+        format!("{:16}", doc),
+        "items =\n    [\n        alpha,\n        beta,\n        gamma\n    ]"
+    );
+
+    // The value is indented relative to the key
+    let nested = const_text("config:") + indent(2, nl() + key_value("key_name", items));
+    assert_str_eq!(format!("{:26}", nested), "config:\n  key_name =\n      [alpha, beta, gamma]");
+}
+
+#[test]
+fn render_ctx_shares_interned_text() {
+    struct Ident(String);
+    impl PrettyPrintWith<RenderCtx> for Ident {
+        fn render_with(&self, ctx: &RenderCtx) -> Document {
+            ctx.text(&self.0)
+        }
+    }
+
+    let ctx = RenderCtx::new();
+    let ident = |name: &str| Ident(String::from(name));
+    let a = ident("counter").render_with(&ctx);
+    let b = ident("counter").render_with(&ctx);
+    let c = ident("total").render_with(&ctx);
+    let (Document::SharedText(a, 7), Document::SharedText(b, 7), Document::SharedText(c, 5)) =
+        (&a, &b, &c)
+    else {
+        panic!("expected interned text to be shared");
+    };
+    assert_eq!(a.as_ptr(), b.as_ptr());
+    assert_ne!(a.as_ptr(), c.as_ptr());
+    assert_eq!(ctx.len(), 2);
+
+    // Interned text is laid out exactly like the text itself, with any configuration
+    let idents = alloc::vec![ident("counter"), ident("total"), ident("counter")];
+    let interned = idents.render_with(&ctx);
+    let plain = pretty_list(idents.iter().map(|ident| text(&ident.0)));
+    for config in [
+        PrintConfig::new(12),
+        PrintConfig::new(12).with_indent_string("\t"),
+        PrintConfig::new(5).with_split_long_text(true),
+    ] {
+        assert_str_eq!(render_with_config(&interned, &config), render_with_config(&plain, &config));
+    }
+    assert_eq!(ctx.text("counter").reindent(4), ctx.text("counter"));
+    assert_str_eq!(format!("{}", interned), "[counter, total, counter]");
+    assert_eq!(ctx.len(), 2);
+
+    let mut ctx = ctx;
+    ctx.clear();
+    assert!(ctx.is_empty());
+    assert_eq!(ctx.text("x"), text("x"));
+    assert!(ctx.text("").is_empty());
+}
+
+/// Returns the visual width of `line` with tab stops every `tab_width` columns
+fn visual_width(line: &str, tab_width: usize) -> usize {
+    line.chars().fold(0, |col, c| match c {
+        '\t' => col + tab_width - (col % tab_width),
+        c => col + unicode_width::UnicodeWidthChar::width(c).unwrap_or(0),
+    })
+}
+
+#[test]
+fn tabs_advance_to_next_tab_stop() {
+    // The width of the tab depends on the column it is printed at
+    for (prefix, tab_width, expected_width) in
+        [("", 8, 9), ("ab", 8, 9), ("abcdefgh", 8, 17), ("ab", 4, 5), ("abcd", 4, 9)]
+    {
+        let line = const_text("\tx");
+        let doc = text(prefix) + (line.clone() | (nl() + line));
+        assert_eq!(visual_width(&format!("{prefix}\tx"), tab_width), expected_width);
+
+        // Fits exactly
+        let config = PrintConfig::new(expected_width).with_tab_width(tab_width as u32);
+        let actual = render_with_config(&doc, &config);
+        assert_str_eq!(actual, format!("{prefix}\tx"));
+
+        // Does not fit
+        let config = PrintConfig::new(expected_width - 1).with_tab_width(tab_width as u32);
+        let actual = render_with_config(&doc, &config);
+        assert_str_eq!(actual, format!("{prefix}\n\tx"));
+        assert_eq!(visual_width(actual.lines().last().unwrap(), tab_width), tab_width + 1);
+    }
+}
+
+#[test]
+fn tabs_are_expanded_when_configured() {
+    // A tab-indented snippet, whose tabs advance to the next multiple of 4 columns
+    let snippet = const_text("if x {") + nl() + "\tab\tc" + nl() + '}';
+    let doc = const_text("code:") + indent(2, nl() + snippet);
+    let config = PrintConfig::new(80).with_tab_width(4);
+    assert_str_eq!(render_with_config(&doc, &config), "code:\n  if x {\n  \tab\tc\n  }");
+    // With the default tab stops every 8 columns, `  \tab\tc` is 17 columns wide
+    assert_eq!(doc.measure_at(80).max_line_width, 17);
+
+    let expanded = config.clone().with_expand_tabs(true);
+    let output = render_with_config(&doc, &expanded);
+    assert_str_eq!(output, "code:\n  if x {\n    ab  c\n  }");
+    // The expanded output is exactly as wide as the tabs were measured to be
+    let widest = output.lines().map(|line| line.len()).max().unwrap();
+    assert_eq!(
+        widest,
+        render_with_config(&doc, &config)
+            .lines()
+            .map(|l| visual_width(l, 4))
+            .max()
+            .unwrap()
+    );
+
+    // Layout decisions account for the width of tabs, whether or not they are expanded
+    let line = Document::from('\t') + "x";
+    let choice = const_text("ab") + (line.clone() | (nl() + line));
+    let config = PrintConfig::new(5).with_tab_width(4).with_expand_tabs(true);
+    assert_str_eq!(render_with_config(&choice, &config), "ab  x");
+    let config = PrintConfig::new(4).with_tab_width(4).with_expand_tabs(true);
+    assert_str_eq!(render_with_config(&choice, &config), "ab\n    x");
+
+    // Tabs used for indentation are expanded too
+    let config = PrintConfig::new(80)
+        .with_indent_string("\t")
+        .with_tab_width(4)
+        .with_expand_tabs(true);
+    assert_str_eq!(
+        render_with_config(&(const_text("a") + indent(1, nl() + "b")), &config),
+        "a\n    b"
+    );
+}
+
+#[test]
+fn prefix_lines_comments_out_broken_document() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let doc =
+        const_text("mod foo {") + indent(4, nl() + prefix_lines("// ", ast.render())) + nl() + '}';
+    let expected = "\
+mod foo {
+    // fn square_plus_1(
+    //     a: number,
+    //     b: number
+    // ) -> number = {
+    //     let c = a * b in c + 1
+    // }
+}";
+    assert_str_eq!(format!("{:40}", doc), expected);
+
+    let doc = const_text("mod foo {")
+        + indent(4, nl() + prefix_lines_after_indent("> ", ast.render()))
+        + nl()
+        + '}';
+    let expected = "\
+mod foo {
+    > fn square_plus_1(
+        > a: number,
+        > b: number
+    > ) -> number = {
+        > let c = a * b in c + 1
+    > }
+}";
+    assert_str_eq!(format!("{:40}", doc), expected);
+
+    // Blank lines are prefixed, without trailing whitespace
+    let doc = prefix_lines("// ", text("a") + nl() + nl() + text("b"));
+    assert_str_eq!(doc.to_string(), "// a\n//\n// b");
+}
+
+fn text_width(doc: &Document) -> u32 {
+    match doc {
+        Document::Char(_, width) | Document::Text(_, width) => *width,
+        doc => panic!("expected text, got {doc:?}"),
+    }
+}
+
+#[test]
+fn grapheme_cluster_widths() {
+    // These sequences are measured as a single glyph in either configuration
+    assert_eq!(text_width(&text("👨\u{200d}👩\u{200d}👧")), 2);
+    assert_eq!(text_width(&text("👍🏽")), 2);
+    assert_eq!(text_width(&text("e\u{301}")), 1);
+    assert_eq!(text_width(&text("🇺🇸")), 2);
+    assert_eq!(text_width(&text("日本")), 4);
+
+    // A conjunct is a single grapheme cluster, but consists of multiple characters of width 1
+    #[cfg(not(feature = "grapheme"))]
+    assert_eq!(text_width(&text("क्षि")), 3);
+    #[cfg(feature = "grapheme")]
+    assert_eq!(text_width(&text("क्षि")), 2);
+
+    // When splitting text, grapheme clusters are kept intact only when measured as such
+    let config = PrintConfig::new(4).with_split_long_text(true);
+    let doc = text("👨\u{200d}👩\u{200d}👧".repeat(3));
+    #[cfg(not(feature = "grapheme"))]
+    assert_str_eq!(
+        render_with_config(&doc, &config),
+        "👨\u{200d}👩\u{200d}\n👧👨\u{200d}\n👩\u{200d}👧\n👨\u{200d}👩\u{200d}\n👧"
+    );
+    #[cfg(feature = "grapheme")]
+    assert_str_eq!(
+        render_with_config(&doc, &config),
+        "👨\u{200d}👩\u{200d}👧👨\u{200d}👩\u{200d}👧\n👨\u{200d}👩\u{200d}👧"
+    );
+}
+
+/// Removes ANSI escape sequences of the form `ESC [ ... m` from `s`
+fn strip_sgr(s: &str) -> String {
+    let mut output = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('\x1b') {
+        output.push_str(&rest[..start]);
+        let end = rest[start..].find('m').unwrap();
+        rest = &rest[(start + end + 1)..];
+    }
+    output.push_str(rest);
+    output
+}
+
+#[test]
+fn ansi_escapes_are_zero_width() {
+    let plain = (0..8u32).map(|n| text(format!("item{n}"))).collect::<Vec<_>>();
+    let colored = (0..8u32)
+        .map(|n| ansi_text(format!("\x1b[1;3{}mitem{n}\x1b[0m", n % 8)))
+        .collect::<Vec<_>>();
+    let plain = render_collection("[", "]", plain.into_iter());
+    let colored = render_collection("[", "]", colored.into_iter());
+    for width in [10, 40, 60, 80] {
+        let expected = format!("{:width$}", plain);
+        let actual = format!("{:width$}", colored);
+        assert_ne!(actual, expected);
+        assert_str_eq!(strip_sgr(&actual), expected);
+    }
+
+    // Escapes are never split
+    let config = PrintConfig::new(3).with_split_long_text(true);
+    let doc = ansi_text("\x1b[31mabcdef\x1b[0m");
+    assert_str_eq!(render_with_config(&doc, &config), "\x1b[31mabc\ndef\x1b[0m");
+
+    // Malformed and truncated sequences
+    assert_eq!(text_width(&ansi_text("\x1b[31")), 0);
+    assert_eq!(text_width(&ansi_text("ab\x1b")), 2);
+    assert_eq!(text_width(&ansi_text("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07")), 4);
+    assert_eq!(text_width(&ansi_text("\x1b]unterminated")), 0);
+    assert_eq!(text_width(&ansi_text("\x1bMab")), 2);
+}
+
+#[test]
+fn printer_can_be_reused() {
+    let printer = Printer::new(PrintConfig::new(20).with_initial_indent(2));
+    assert_eq!(printer.config().width(), 20);
+
+    let list = vec![1u32, 2, 3];
+    assert_str_eq!(printer.print(&list.render()), "[1, 2, 3]");
+
+    let list = vec![100000u32, 200000, 300000];
+    assert_str_eq!(
+        printer.print(&list.render()),
+        "[\n      100000,\n      200000,\n      300000\n  ]"
+    );
+
+    let mut output = String::from("x = ");
+    printer.print_to(&text("foo"), &mut output).unwrap();
+    assert_str_eq!(output, "x = foo");
+}
+
+#[test]
+fn printer_tracks_column_across_documents() {
+    let list = vec![1u32, 2, 3];
+    // The configuration can be borrowed rather than copied
+    let config = PrintConfig::new(20);
+    let mut printer = Printer::from(&config);
+    assert_eq!((printer.column(), printer.line()), (0, 0));
+
+    // On its own, the list fits on a single line
+    assert_str_eq!(printer.print(&list.render()), "[1, 2, 3]");
+
+    let mut output = String::new();
+    printer.print_doc(&const_text("let values = "), &mut output).unwrap();
+    assert_eq!((printer.column(), printer.line()), (13, 0));
+
+    // But not after the text already on the line
+    printer.print_doc(&list.render(), &mut output).unwrap();
+    assert_str_eq!(output, "let values = [\n    1,\n    2,\n    3\n]");
+    assert_eq!((printer.column(), printer.line()), (1, 4));
+
+    printer.print_doc(&const_text(";"), &mut output).unwrap();
+    assert_eq!((printer.column(), printer.line()), (2, 4));
+
+    // Output written by other means can be accounted for
+    output.push_str(" // ");
+    printer.set_column(printer.column() + 4);
+    printer.print_doc(&text("comment"), &mut output).unwrap();
+    assert_str_eq!(output, "let values = [\n    1,\n    2,\n    3\n]; // comment");
+    assert_eq!((printer.column(), printer.line()), (13, 4));
+}
+
+#[test]
+fn indent_string_width_affects_layout() {
+    let list = vec![1u32, 2, 3];
+    let doc = const_text("items:") + indent(4, nl() + list.render());
+
+    // With the default indentation, the list does not fit at its indentation
+    let config = PrintConfig::new(12);
+    assert_str_eq!(
+        render_with_config(&doc, &config),
+        "items:\n    [\n        1,\n        2,\n        3\n    ]"
+    );
+
+    // But it does when indenting by the narrower indentation string
+    let config = PrintConfig::new(12).with_indent_string("| ");
+    assert_str_eq!(render_with_config(&doc, &config), "items:\n| [1, 2, 3]");
+
+    let config = PrintConfig::new(10).with_indent_string("| ");
+    assert_str_eq!(render_with_config(&doc, &config), "items:\n| [\n| | 1,\n| | 2,\n| | 3\n| ]");
+
+    // Indentation given in columns is filled with as many levels as fit
+    let config = PrintConfig::new(20)
+        .with_indent_string("| ")
+        .with_initial_indent(3)
+        .with_indent_first_line(true);
+    assert_str_eq!(render_with_config(&doc, &config), "|  items:\n| |  [1, 2, 3]");
+
+    // Indenting using tabs is a special case
+    let config = PrintConfig::new(12).with_indent_string("\t").with_tab_width(2);
+    assert_str_eq!(render_with_config(&doc, &config), "items:\n\t[1, 2, 3]");
+}
+
+#[test]
+fn numeric_wrappers_render_as_inner_value() {
+    use core::num::{Saturating, Wrapping};
+
+    assert_str_eq!(Wrapping(5u32).to_pretty_string(), "5");
+    assert_str_eq!(Saturating(-5i64).to_pretty_string(), "-5");
+    assert_str_eq!(vec![Wrapping(1u8), Wrapping(2)].to_pretty_string(), "[1, 2]");
+}
+
+#[test]
+fn option_rendering() {
+    assert_str_eq!(Some(5u32).to_pretty_string(), "some(5)");
+    assert_str_eq!(None::<u32>.to_pretty_string(), "none");
+
+    let nested: [Option<Option<u32>>; 3] = [Some(Some(1)), Some(None), None];
+    let nested = nested.iter().map(PrettyPrint::to_pretty_string).collect::<Vec<_>>();
+    assert_eq!(nested, ["some(some(1))", "some(none)", "none"]);
+
+    assert_str_eq!(vec![Some(1u32), None, Some(3)].to_pretty_string(), "[some(1), none, some(3)]");
+
+    let value = Some(vec![100000u32, 200000, 300000]);
+    assert_str_eq!(
+        render_with_config(&value.render(), &PrintConfig::new(20)),
+        "some([\n    100000,\n    200000,\n    300000\n])"
+    );
+}
+
+#[test]
+fn result_rendering() {
+    assert_str_eq!(Ok::<u32, u32>(1).to_pretty_string(), "ok(1)");
+    assert_str_eq!(Err::<u32, _>("failed").to_pretty_string(), "err(failed)");
+
+    let result: Result<u32, _> = Err(vec!["first error message", "second error message"]);
+    assert_str_eq!(
+        render_with_config(&result.render(), &PrintConfig::new(30)),
+        "err([\n    first error message,\n    second error message\n])"
+    );
+
+    let result: Result<u32, _> = Err("the operation failed for some reason");
+    assert_str_eq!(
+        render_with_config(&result.render(), &PrintConfig::new(30)),
+        "err(\n    the operation failed for some reason\n)"
+    );
+
+    // Multi-line payloads are indented as a whole in the broken layout
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let result: Result<u32, _> = Err(ast);
+    let expected = "\
+err(
+    fn square_plus_1(
+        a: number,
+        b: number
+    ) -> number = {
+        let c =
+            a * b
+         in c + 1
+    }
+)";
+    assert_str_eq!(render_with_config(&result.render(), &PrintConfig::new(20)), expected);
+}
+
+#[test]
+fn measure_at_matches_rendered_output() {
+    let doc = const_text("first") + indent(4, nl() + "second line") + nl() + "end";
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "first\n    second line\nend");
+    let expected = Measurement {
+        lines: 3,
+        max_line_width: 15,
+        final_column: 3,
+    };
+    assert_eq!(doc.measure_at(80), expected);
+
+    let list = vec![100000u32, 200000, 300000].render();
+    let expected = Measurement {
+        lines: 1,
+        max_line_width: 24,
+        final_column: 24,
+    };
+    assert_eq!(list.measure_at(80), expected);
+    assert_str_eq!(
+        render_with_config(&list, &PrintConfig::new(20)),
+        "[\n    100000,\n    200000,\n    300000\n]"
+    );
+    let expected = Measurement {
+        lines: 5,
+        max_line_width: 11,
+        final_column: 1,
+    };
+    assert_eq!(list.measure_at(20), expected);
+
+    let expected = Measurement {
+        lines: 0,
+        max_line_width: 0,
+        final_column: 0,
+    };
+    assert_eq!(Document::Empty.measure_at(80), expected);
+}
+
+#[test]
+fn slices_arrays_and_vecs_render_identically() {
+    fn check<const N: usize>(array: [u32; N]) {
+        let vec = array.to_vec();
+        for width in [0, 20, 80] {
+            let config = PrintConfig::new(width);
+            let expected = render_with_config(&vec.render(), &config);
+            assert_str_eq!(render_with_config(&array.render(), &config), expected);
+            assert_str_eq!(render_with_config(&vec.as_slice().render(), &config), expected);
+        }
+    }
+
+    check([]);
+    check([1]);
+    check([100000, 200000, 300000]);
+
+    let slice: &[u32] = &[1, 2, 3];
+    assert_str_eq!(slice.to_pretty_string(), "[1, 2, 3]");
+    assert_str_eq!([[1u8; 2]; 2].to_pretty_string(), "[[1, 1], [1, 1]]");
+}
+
+#[test]
+fn float_rendering() {
+    let render = |doc: Document| render_with_config(&doc, &PrintConfig::new(80));
+
+    assert_str_eq!((0.1f64 + 0.2).to_pretty_string(), "0.30000000000000004");
+    assert_str_eq!(0.1f32.to_pretty_string(), "0.1");
+    assert_str_eq!(1.0f64.to_pretty_string(), "1.0");
+    assert_str_eq!((-0.0f64).to_pretty_string(), "-0.0");
+    assert_str_eq!(1e16f64.to_pretty_string(), "1e16");
+    assert_str_eq!(f64::from_bits(1).to_pretty_string(), "5e-324");
+    assert_str_eq!(f32::from_bits(1).to_pretty_string(), "1e-45");
+    assert_str_eq!(f64::NAN.to_pretty_string(), "NaN");
+    assert_str_eq!(f32::INFINITY.to_pretty_string(), "inf");
+    assert_str_eq!(f64::NEG_INFINITY.to_pretty_string(), "-inf");
+
+    assert_str_eq!(render(float(core::f64::consts::PI, Some(2))), "3.14");
+    assert_str_eq!(render(float(2.0, Some(3))), "2.000");
+    assert_str_eq!(render(float(0.125, Some(2))), "0.12");
+    assert_str_eq!(render(float(0.375, Some(2))), "0.38");
+    assert_str_eq!(render(float(-0.0, Some(1))), "-0.0");
+    assert_str_eq!(render(float(f64::from_bits(1), Some(3))), "0.000");
+    assert_str_eq!(render(float(f64::from(0.1f32), Some(10))), "0.1000000015");
+    assert_str_eq!(render(float(f64::NAN, Some(2))), "NaN");
+    assert_str_eq!(render(float(f64::NEG_INFINITY, Some(2))), "-inf");
+}
+
+#[test]
+fn cell_rendering() {
+    use core::cell::{Cell, RefCell};
+
+    let cell = Cell::new(5u32);
+    assert_str_eq!(cell.to_pretty_string(), "5");
+    cell.set(6);
+    assert_str_eq!(cell.to_pretty_string(), "6");
+
+    let cell = RefCell::new(vec![1u32, 2, 3]);
+    assert_str_eq!(cell.to_pretty_string(), "[1, 2, 3]");
+    {
+        let _shared = cell.borrow();
+        assert_str_eq!(cell.to_pretty_string(), "[1, 2, 3]");
+    }
+    {
+        let mut value = cell.borrow_mut();
+        value.push(4);
+        assert_str_eq!(cell.to_pretty_string(), "<borrowed>");
+    }
+    assert_str_eq!(cell.to_pretty_string(), "[1, 2, 3, 4]");
+}
+
+#[test]
+fn char_and_unit_rendering() {
+    assert_str_eq!('a'.to_pretty_string(), "a");
+    assert_eq!('\n'.render(), Document::Newline);
+    let doc = 'a'.render() + indent(4, '\n'.render() + 'b');
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "a\n    b");
+
+    assert_str_eq!(().to_pretty_string(), "()");
+    assert_str_eq!(Ok::<(), u32>(()).to_pretty_string(), "ok(())");
+    assert_str_eq!(vec![(), ()].to_pretty_string(), "[(), ()]");
+}
+
+#[test]
+fn line_or_rendering() {
+    let args = text("first") + line_or(", ") + text("second") + line_or(", ") + text("third");
+    let doc = const_text("(") + indent(4, line_or("") + args) + line_or("") + ")";
+    let doc = group_or_break(doc, false);
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "(first, second, third)");
+    assert_str_eq!(
+        render_with_config(&doc, &PrintConfig::new(10)),
+        "(\n    first\n    second\n    third\n)"
+    );
+}
+
+#[test]
+fn pretty_print_to_string() {
+    let list = vec![100000u32, 200000, 300000].render();
+
+    let mut output = String::from("list = ");
+    pretty_print_to(&list, 80, &mut output).unwrap();
+    assert_str_eq!(output, "list = [100000, 200000, 300000]");
+
+    let mut output = String::new();
+    pretty_print_to(&list, 20, &mut output).unwrap();
+    assert_str_eq!(output, "[\n    100000,\n    200000,\n    300000\n]");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn hash_collections_render_deterministically() {
+    use std::collections::{HashMap, HashSet};
+
+    // Every map has its own random hash seed, so the iteration order differs between them
+    for _ in 0..10 {
+        let set = (1..=12u32).collect::<HashSet<_>>();
+        assert_str_eq!(set.to_pretty_string(), "{1, 10, 11, 12, 2, 3, 4, 5, 6, 7, 8, 9}");
+
+        let map = ["one", "two", "three", "four"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name, i + 1))
+            .collect::<HashMap<_, _>>();
+        assert_str_eq!(map.to_pretty_string(), "{four => 4, one => 1, three => 3, two => 2}");
+        assert_str_eq!(
+            render_with_config(&map.render(), &PrintConfig::new(20)),
+            "{\n    four => 4,\n    one => 1,\n    three => 3,\n    two => 2\n}"
+        );
+    }
+
+    assert_str_eq!(HashMap::<u32, u32>::new().to_pretty_string(), "{}");
+}
+
+#[test]
+fn mutable_references_render_like_their_referent() {
+    fn render_generic<T: PrettyPrint>(value: T) -> String {
+        value.to_pretty_string()
+    }
+
+    let mut string = String::from("hello");
+    assert_str_eq!(render_generic(&mut string), "hello");
+    let mut list = vec![1u32, 2, 3];
+    let slice: &mut [u32] = &mut list;
+    assert_str_eq!(render_generic(slice), "[1, 2, 3]");
+    assert_str_eq!(format!("{}", Pretty(&&mut string)), "hello");
+}
+
+#[test]
+fn tabstop_aligns_columns() {
+    let rows = [("main", "fn() -> u32", "0x100"), ("counter", "u32", "3"), ("x", "felt", "0")];
+    let table = rows
+        .iter()
+        .map(|(name, ty, value)| text(*name) + tabstop(20) + text(*ty) + tabstop(40) + text(*value))
+        .reduce(|table, row| table + nl() + row)
+        .unwrap();
+    let output = render_with_config(&table, &PrintConfig::new(80));
+    assert_str_eq!(
+        output,
+        "\
+main                fn() -> u32         0x100
+counter             u32                 3
+x                   felt                0"
+    );
+    for line in output.lines() {
+        assert_eq!(line[20..].chars().next().map(|c| c != ' '), Some(true));
+    }
+
+    // Columns include indentation, and overlong fields are followed by a single space
+    let doc = const_text("symbols:")
+        + indent(4, nl() + text("a_very_long_symbol_name") + tabstop(20) + "u32")
+        + indent(4, nl() + text("b") + tabstop(20) + "u8");
+    assert_str_eq!(
+        render_with_config(&doc, &PrintConfig::new(80)),
+        "symbols:\n    a_very_long_symbol_name u32\n    b               u8"
+    );
+
+    // Padding at the end of a line is trailing whitespace, and so is not printed
+    assert_str_eq!(
+        render_with_config(&(text("a") + tabstop(10) + nl()), &PrintConfig::new(80)),
+        "a\n"
+    );
+
+    // The padding is taken into account when deciding whether a layout fits
+    let doc = text("name") + tabstop(20) + (text("value") | (nl() + text("value")));
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(24)), "name\nvalue");
+}
+
+#[test]
+fn wrappers_forward_pretty_print() {
+    use core::cell::{Cell, RefCell};
+
+    /// A type which prints differently than it renders, to check that printing is forwarded
+    #[derive(Copy, Clone)]
+    struct Custom;
+
+    impl PrettyPrint for Custom {
+        fn render(&self) -> Document {
+            const_text("rendered")
+        }
+        fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("printed")
+        }
+        fn to_pretty_string(&self) -> String {
+            String::from("printed")
+        }
+    }
+
+    let mut custom = Custom;
+    let cell = Cell::new(Custom);
+    let ref_cell = RefCell::new(Custom);
+    assert_str_eq!(format!("{}", Pretty(&&mut custom)), "printed");
+    assert_str_eq!(PrettyPrint::to_pretty_string(&&mut custom), "printed");
+    assert_str_eq!(format!("{}", Pretty(&cell)), "printed");
+    assert_str_eq!(cell.to_pretty_string(), "printed");
+    assert_str_eq!(format!("{}", Pretty(&ref_cell)), "printed");
+    assert_str_eq!(ref_cell.to_pretty_string(), "printed");
+    assert_str_eq!(render_with_config(&ref_cell.render(), &PrintConfig::new(80)), "rendered");
+
+    let _borrowed = ref_cell.borrow_mut();
+    assert_str_eq!(format!("{}", Pretty(&ref_cell)), "<borrowed>");
+    assert_str_eq!(ref_cell.to_pretty_string(), "<borrowed>");
+}
+
+#[test]
+fn range_rendering() {
+    assert_str_eq!((1u32..10).to_pretty_string(), "1..10");
+    assert_str_eq!((1u32..=10).to_pretty_string(), "1..=10");
+    assert_str_eq!((1u32..).to_pretty_string(), "1..");
+    assert_str_eq!((..10u32).to_pretty_string(), "..10");
+    assert_str_eq!((..=10u32).to_pretty_string(), "..=10");
+    assert_str_eq!((..).to_pretty_string(), "..");
+
+    // A range is never broken, only the list containing it
+    let ranges = vec![0u32..100000, 100000..200000, 200000..300000];
+    assert_str_eq!(ranges.to_pretty_string(), "[0..100000, 100000..200000, 200000..300000]");
+    assert_str_eq!(
+        render_with_config(&ranges.render(), &PrintConfig::new(20)),
+        "[\n    0..100000,\n    100000..200000,\n    200000..300000\n]"
+    );
+    let ranges = vec![vec![1u32, 2, 3]..vec![4, 5, 6]];
+    assert_str_eq!(
+        render_with_config(&ranges.render(), &PrintConfig::new(10)),
+        "[\n    [1, 2, 3]..[4, 5, 6]\n]"
+    );
+}
+
+#[test]
+fn explain_reports_group_layouts() {
+    let args = group(text("first_argument") + line_or(", ") + text("second_argument"));
+    let doc = const_text("call(") + indent(4, args) + ")";
+    assert_str_eq!(
+        doc.explain_at(80),
+        "line 1, column 6: group flat, as the line is 37 columns wide, within the page width of 80"
+    );
+    assert_str_eq!(
+        doc.explain_at(30),
+        "line 1, column 6: group broken, as the line would be 37 columns wide if flat, exceeding \
+         the page width of 30"
+    );
+
+    let id = GroupId::new(3);
+    let doc = group_or_break(
+        text("a") + line_or(" ") + group_with_id(id, text("b") + line_or(" ") + "c"),
+        true,
+    );
+    assert_str_eq!(
+        doc.explain_at(80),
+        "line 1, column 1: group broken, as it is always broken\nline 2, column 1: group 3 flat, as \
+         the line is 3 columns wide, within the page width of 80"
+    );
+    assert!(Document::Empty.explain_at(80).is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn network_address_rendering() {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    assert_str_eq!(Ipv4Addr::LOCALHOST.to_pretty_string(), "127.0.0.1");
+    assert_str_eq!(IpAddr::V6(Ipv6Addr::LOCALHOST).to_pretty_string(), "::1");
+
+    let nodes = [
+        (SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 8080), 1u64),
+        (SocketAddr::new(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).into(), 443), 2),
+    ]
+    .into_iter()
+    .collect::<alloc::collections::BTreeMap<_, _>>();
+    assert_str_eq!(nodes.to_pretty_string(), "{10.0.0.1:8080 => 1, [fe80::1]:443 => 2}");
+    assert_str_eq!(
+        render_with_config(&nodes.render(), &PrintConfig::new(30)),
+        "{\n    10.0.0.1:8080 => 1,\n    [fe80::1]:443 => 2\n}"
+    );
+}
+
+#[cfg(feature = "core-net")]
+#[test]
+fn core_network_address_rendering() {
+    use core::net::{Ipv4Addr, SocketAddrV4};
+
+    assert_str_eq!(Ipv4Addr::new(192, 168, 0, 1).to_pretty_string(), "192.168.0.1");
+    let addrs = [
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, 80),
+        SocketAddrV4::new(Ipv4Addr::BROADCAST, 0),
+    ];
+    assert_str_eq!(addrs.to_pretty_string(), "[127.0.0.1:80, 255.255.255.255:0]");
+}
+
+#[test]
+fn debug_tree_shows_structure() {
+    let id = GroupId::new(1);
+    let doc = const_text("fn")
+        + indent(4, nl() + group_with_id(id, text("a") + (const_text(" ") | nl()) + 'b'))
+        + if_group_breaks(id, nl(), Document::Empty)
+        + prefix_lines("// ", tabstop(8));
+    let expected = r#"Concat
+  Text("fn", w=2)
+  Indent(4)
+    Concat
+      Newline
+      Group(id=1)
+        Concat
+          Char('a', w=1)
+          Choice
+            Char(' ', w=1)
+            Newline
+          Char('b', w=1)
+  IfBreak(id=1)
+    Newline
+    Empty
+  Prefix("// ")
+    TabStop(8)"#;
+    assert_str_eq!(doc.debug_tree(), expected);
+    assert_str_eq!(Document::Empty.debug_tree(), "Empty");
+}
+
+#[test]
+fn atomic_rendering() {
+    use core::{
+        num::Wrapping,
+        sync::atomic::{AtomicI8, AtomicU64, AtomicUsize, Ordering},
+    };
+
+    struct Stats {
+        requests: AtomicU64,
+        connections: AtomicUsize,
+        generation: Wrapping<u32>,
+    }
+
+    impl PrettyPrint for Stats {
+        fn render(&self) -> Document {
+            let fields = [
+                const_text("requests: ") + self.requests.render(),
+                const_text("connections: ") + self.connections.render(),
+                const_text("generation: ") + self.generation.render(),
+            ];
+            const_text("stats ") + render_collection("{", "}", fields.into_iter())
+        }
+    }
+
+    let stats = Stats {
+        requests: AtomicU64::new(u64::MAX),
+        connections: AtomicUsize::new(3),
+        generation: Wrapping(u32::MAX) + Wrapping(2),
+    };
+    stats.connections.fetch_add(1, Ordering::Relaxed);
+    assert_str_eq!(
+        stats.to_pretty_string(),
+        "stats {requests: 18446744073709551615, connections: 4, generation: 1}"
+    );
+    assert_str_eq!(AtomicI8::new(-1).to_pretty_string(), "-1");
+}
+
+#[test]
+fn to_pretty_string_preallocates() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let list = vec![100000u32, 200000, 300000];
+    let long = (0..100).collect::<Vec<u32>>();
+    assert_str_eq!(ast.to_pretty_string(), format!("{}", Pretty(&ast)));
+    assert_str_eq!(list.to_pretty_string(), format!("{}", Pretty(&list)));
+    assert_str_eq!(long.to_pretty_string(), format!("{}", Pretty(&long)));
+
+    // Output which fits on one line never outgrows the initial allocation
+    let output = list.to_pretty_string();
+    let hint = print::capacity_hint(&list.render());
+    assert_eq!(output.len(), hint);
+    assert_eq!(output.capacity(), String::with_capacity(hint).capacity());
+}
+
+#[test]
+fn wrappers_delegate_to_pretty_string() {
+    /// A type which prints differently from the document it renders
+    struct Custom;
+
+    impl PrettyPrint for Custom {
+        fn render(&self) -> Document {
+            const_text("rendered")
+        }
+        fn to_pretty_string_with_width(&self, _width: usize) -> String {
+            String::from("custom")
+        }
+        fn pretty_print(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("custom")
+        }
+    }
+
+    assert_str_eq!(Custom.to_pretty_string(), "custom");
+    assert_str_eq!(alloc::boxed::Box::new(Custom).to_pretty_string(), "custom");
+    assert_str_eq!(Rc::new(Custom).to_pretty_string(), "custom");
+    assert_str_eq!(alloc::sync::Arc::new(Custom).to_pretty_string_with_width(10), "custom");
+    assert_str_eq!(core::num::Wrapping(Custom).to_pretty_string(), "custom");
+    assert_str_eq!(format!("{}", Pretty(&alloc::boxed::Box::new(Custom))), "custom");
+}
+
+#[test]
+fn render_with_streams_events() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let doc = ast.render();
+    for width in [20, 80] {
+        let config = PrintConfig::new(width);
+        let mut output = String::new();
+        let mut lines = vec![String::new()];
+        render_with(&doc, &config, |event| {
+            match event {
+                RenderEvent::Text(text) => {
+                    output.push_str(text);
+                    lines.last_mut().unwrap().push_str(text);
+                },
+                RenderEvent::Newline => {
+                    output.push('\n');
+                    lines.push(String::new());
+                },
+                RenderEvent::Indent(n) => {
+                    output.extend(core::iter::repeat(' ').take(n as usize));
+                    lines.last_mut().unwrap().extend(core::iter::repeat(' ').take(n as usize));
+                },
+                RenderEvent::SpanStart(_) | RenderEvent::SpanEnd(_) => (),
+            }
+            Ok(())
+        })
+        .unwrap();
+        let expected = format!("{:width$}", doc);
+        assert_str_eq!(output, expected);
+        assert_eq!(lines, expected.lines().collect::<Vec<_>>());
+    }
+
+    // Printing stops as soon as the sink returns an error
+    let config = PrintConfig::new(20);
+    let mut events = vec![];
+    let mut newlines = 0;
+    let result = render_with(&doc, &config, |event| {
+        if event == RenderEvent::Newline {
+            newlines += 1;
+            if newlines == 2 {
+                return Err(fmt::Error);
+            }
+        }
+        events.push(format!("{event:?}"));
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert_eq!(
+        events,
+        [
+            "Text(\"fn\")",
+            "Text(\" \")",
+            "Text(\"square_plus_1\")",
+            "Text(\"(\")",
+            "Newline",
+            "Indent(4)",
+            "Text(\"a\")",
+            "Text(\":\")",
+            "Text(\" \")",
+            "Text(\"number\")",
+            "Text(\",\")",
+        ]
+    );
+}
+
+#[test]
+fn pretty_print_output_limits() {
+    let doc = (0..10)
+        .map(|i| text(format!("line {i}")))
+        .reduce(|acc, line| acc + nl() + line)
+        .unwrap();
+
+    let config = PrintConfig::new(80).with_max_lines(Some(3));
+    assert_str_eq!(render_with_config(&doc, &config), "line 0\nline 1\nline 2\n…");
+
+    let config = PrintConfig::new(80).with_max_lines(Some(10));
+    assert_str_eq!(
+        render_with_config(&doc, &config),
+        render_with_config(&doc, &PrintConfig::new(80))
+    );
+
+    // With no lines to spare, only the elision marker is printed
+    let config = PrintConfig::new(80).with_max_lines(Some(0));
+    assert_str_eq!(render_with_config(&doc, &config), "…");
+    assert_str_eq!(format!("{doc:80.0}"), "…");
+    assert_str_eq!(render_with_config(&Document::Empty, &config), "");
+
+    let config = PrintConfig::new(80).with_max_bytes(Some(10)).with_elision_marker("[...]");
+    assert_str_eq!(render_with_config(&doc, &config), "line 0\nlin\n[...]");
+
+    // The byte limit never splits a character
+    let config = PrintConfig::new(80).with_max_bytes(Some(2));
+    assert_str_eq!(render_with_config(&text("aé"), &config), "a\n…");
+
+    // Printing stops once the limit is reached, rather than traversing the rest of the document.
+    // The document is built as a balanced tree to avoid deep recursion when it is dropped.
+    fn lines(range: core::ops::Range<usize>) -> Document {
+        if range.len() == 1 {
+            return text(format!("line {}", range.start));
+        }
+        let mid = range.start + range.len() / 2;
+        lines(range.start..mid) + nl() + lines(mid..range.end)
+    }
+    let huge = lines(0..100_000);
+    let config = PrintConfig::new(80).with_max_lines(Some(5));
+    let mut events = 0;
+    render_with(&huge, &config, |_| {
+        events += 1;
+        Ok(())
+    })
+    .unwrap();
+    assert!(events < 20, "expected printing to stop early, but got {events} events");
+}
+
+#[test]
+fn concat_all_builds_balanced_tree() {
+    fn height(doc: &Document) -> usize {
+        match doc {
+            Document::Concat(a, b) => 1 + core::cmp::max(height(a), height(b)),
+            _ => 1,
+        }
+    }
+
+    let pieces = (0..100_000).map(|i| text(format!("{}", i % 10))).collect::<Vec<_>>();
+    let doc = concat_all(&pieces);
+    assert!(height(&doc) <= 18, "expected a balanced tree, but got height {}", height(&doc));
+
+    let output = render_with_config(&doc, &PrintConfig::new(80));
+    assert_eq!(output.len(), 100_000);
+    assert!(output.starts_with("0123456789012"));
+
+    let doc = concat_vec(vec![const_text("a"), Document::Empty, const_text("b"), const_text("c")]);
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "abc");
+    assert!(concat_all(&[]).is_empty());
+}
+
+#[test]
+fn split_preserves_blank_lines() {
+    let input = "first\n\n\nsecond\r\n\nthird\n";
+    let doc = split(input);
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "first\n\n\nsecond\n\nthird\n");
+
+    let doc = const_text("fn") + indent(4, nl() + split("a\n\nb")) + nl() + const_text("end");
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "fn\n    a\n\n    b\nend");
+
+    assert!(split("").is_empty());
+}
+
+#[test]
+fn flatten_honors_hard_line_breaks() {
+    let args =
+        const_text("a,") + nl() + const_text("b,") + (const_text(" ") | nl()) + const_text("c");
+    let doc = const_text("call(") + indent(4, flatten(args)) + const_text(")");
+    // The line break is honored at the current indentation, and the rest remains flat, even when
+    // it no longer fits
+    for width in [80, 5] {
+        assert_str_eq!(render_with_config(&doc, &PrintConfig::new(width)), "call(a,\n    b, c)");
+    }
+
+    // The same applies to a group which fits on one line up to the line break
+    let doc = const_text("{")
+        + indent(2, group(nl() + const_text("x") + (const_text(" ") | nl()) + const_text("y")));
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "{\n  x y");
+
+    // Line breaks written as characters are treated the same way
+    let doc = indent(4, flatten(const_text("a") + '\n' + const_text("b")));
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "a\n    b");
+}
+
+#[test]
+#[should_panic(expected = "encountered a hard line break in a document being laid out flat")]
+fn strict_flatten_rejects_hard_line_breaks() {
+    let doc = flatten(const_text("a") + nl() + const_text("b"));
+    render_with_config(&doc, &PrintConfig::new(80).with_strict_flatten(true));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn trace_reports_choice_decisions() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let doc = ast.render();
+    let mut decisions = vec![];
+    render_traced(
+        &doc,
+        &PrintConfig::new(30),
+        |event| {
+            decisions.push((event.path.to_vec(), event.remaining, event.left_width, event.branch))
+        },
+        |_| Ok(()),
+    )
+    .unwrap();
+    assert_eq!(
+        decisions,
+        [
+            // The parameter list does not fit on the first line, so it is broken
+            (vec![Branch::Right], 14, Some(36), Branch::Right),
+            // The remaining choices all fit
+            (vec![Branch::Left], 19, Some(10), Branch::Left),
+            (vec![Branch::Left], 13, Some(5), Branch::Left),
+            (vec![Branch::Left, Branch::Left], 9, Some(5), Branch::Left),
+        ]
+    );
+}
+
+#[test]
+fn join_iter_renders_separated_items() {
+    let idents = [Ident::new("a"), Ident::new("b"), Ident::new("c")];
+    let doc = join_iter(const_text(", "), &idents);
+    assert_str_eq!(format!("{doc}"), "a, b, c");
+
+    let doc = join_iter(const_text(", "), &idents[..1]);
+    assert_str_eq!(format!("{doc}"), "a");
+
+    assert!(join_iter(const_text(", "), &idents[..0]).is_empty());
+}
+
+#[test]
+fn render_with_spans_locates_annotated_regions() {
+    let alpha = with_span(2, const_text("alpha"));
+    let beta = with_span(3, const_text("beta"));
+    let args = (alpha.clone() + ", " + beta.clone())
+        | (indent(4, nl() + alpha + "," + nl() + beta) + nl());
+    // Spans which produce no output are omitted
+    let doc = with_span(1, const_text("f(") + args + ")")
+        + with_span(4, Document::Empty)
+        + with_span(5, const_text(" "))
+        + nl();
+
+    let span = |id, bytes: core::ops::Range<usize>, start: (usize, usize), end: (usize, usize)| {
+        SpanEntry {
+            id,
+            start_byte: bytes.start,
+            end_byte: bytes.end,
+            start_line: start.0,
+            start_col: start.1,
+            end_line: end.0,
+            end_col: end.1,
+        }
+    };
+
+    let (output, spans) = render_with_spans(&doc, &PrintConfig::new(80));
+    assert_str_eq!(output, "f(alpha, beta)\n");
+    assert_eq!(
+        spans,
+        [
+            span(1, 0..14, (0, 0), (0, 14)),
+            span(2, 2..7, (0, 2), (0, 7)),
+            span(3, 9..13, (0, 9), (0, 13))
+        ]
+    );
+
+    let (output, spans) = render_with_spans(&doc, &PrintConfig::new(10));
+    assert_str_eq!(output, "f(\n    alpha,\n    beta\n)\n");
+    assert_eq!(
+        spans,
+        [
+            span(1, 0..24, (0, 0), (3, 1)),
+            span(2, 7..12, (1, 4), (1, 9)),
+            span(3, 18..22, (2, 4), (2, 8))
+        ]
+    );
+    for span in spans {
+        assert!(!output[span.start_byte..span.end_byte].starts_with(char::is_whitespace));
+        assert!(!output[span.start_byte..span.end_byte].ends_with(char::is_whitespace));
+    }
+
+    // Tabs extend to the next tab stop, as when laying out the document
+    let doc = text("x\ty") + with_span(6, const_text("z"));
+    let (output, spans) = render_with_spans(&doc, &PrintConfig::new(80).with_tab_width(4));
+    assert_str_eq!(output, "x\tyz");
+    assert_eq!(spans, [span(6, 3..4, (0, 5), (0, 6))]);
+}
+
+#[test]
+fn truncate_caps_width_with_ellipsis() {
+    let config = PrintConfig::new(80);
+
+    // Content which fits is unchanged, including content exactly at the limit
+    let doc = truncate(10, const_text("short"));
+    assert_str_eq!(render_with_config(&doc, &config), "short");
+    let doc = truncate(10, const_text("0123456789"));
+    assert_str_eq!(render_with_config(&doc, &config), "0123456789");
+
+    // Content which exceeds the limit is cut short, with the ellipsis counted towards the limit
+    let doc = truncate(10, const_text("0123456789X")) + " rest";
+    assert_str_eq!(render_with_config(&doc, &config), "012345678… rest");
+
+    // Widths are measured in columns, not characters
+    let doc = truncate(7, text("日本語テキスト"));
+    let output = render_with_config(&doc, &config);
+    assert_str_eq!(output, "日本語…");
+    assert_eq!(visual_width(&output, 8), 7);
+
+    // A line break is never displayed
+    let doc = truncate(10, const_text("a") + nl() + const_text("b"));
+    assert_str_eq!(render_with_config(&doc, &config), "a…");
+
+    // A truncated document is measured by its truncated width when choosing a layout
+    let doc = const_text("[")
+        + truncate(4, const_text("abcdefgh"))
+        + ((const_text(" ") + "x") | (nl() + "x"));
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(7)), "[abc… x");
+
+    // Tabs extend to the next tab stop, including when measuring whether the text overflows
+    let config = PrintConfig::new(80).with_tab_width(4);
+    let doc = const_text("ab") + truncate(6, text("c\tdefgh\tij"));
+    let output = render_with_config(&doc, &config);
+    assert_str_eq!(output, "abc\tdef…");
+    assert_eq!(visual_width(&output, 4), 8);
+    let doc = const_text("ab") + truncate(6, text("c\td"));
+    assert_str_eq!(render_with_config(&doc, &config), "abc\td");
+}
+
+#[test]
+fn formatter_precision_limits_lines() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let output = format!("{:20.3}", Pretty(&ast));
+    assert_str_eq!(output, "fn square_plus_1(\n    a: number,\n    b: number\n…");
+
+    let doc = ast.render();
+    assert_str_eq!(format!("{:20.3}", doc), output);
+
+    // Without a precision, the output is not limited
+    assert_eq!(format!("{:20}", Pretty(&ast)).lines().count(), 8);
+}
+
+#[test]
+fn contains_hardline_ignores_broken_layouts() {
+    assert!(!Document::Empty.contains_hardline());
+    assert!(!(const_text("a") + " " + const_text("b")).contains_hardline());
+    assert!((const_text("a") + nl() + const_text("b")).contains_hardline());
+    assert!(indent(4, group(const_text("a") + '\n')).contains_hardline());
+
+    // Line breaks in the multi-line layout of a choice are not hard line breaks
+    let choice = (const_text("a") + " " + "b") | (const_text("a") + nl() + "b");
+    assert!(!choice.contains_hardline());
+    assert!(!if_break(nl(), const_text(" ")).contains_hardline());
+
+    // ...but those in the flat layout are, including when nested
+    let nested = const_text("x") + ((const_text("a") + (nl() | const_text(" "))) | const_text("b"));
+    assert!(nested.contains_hardline());
+    assert!(!(const_text("x") + ((const_text("a") + (const_text(" ") | nl())) | nl()))
+        .contains_hardline());
+    assert!(if_break(const_text(" "), nl()).contains_hardline());
+
+    // Collection layouts only break lines in their multi-line layout
+    assert!(!vec![1u32, 2, 3].render().contains_hardline());
+}
+
+#[test]
+fn alternate_flag_expands_layout() {
+    let items = vec![1u32, 2, 3];
+    let expanded = "[\n    1,\n    2,\n    3\n]";
+    assert_str_eq!(format!("{}", Pretty(&items)), "[1, 2, 3]");
+    assert_str_eq!(format!("{:#}", Pretty(&items)), expanded);
+    assert_str_eq!(format!("{:width$}", Pretty(&items), width = 5), expanded);
+    assert_str_eq!(format!("{:#}", items.render()), expanded);
+
+    // Explicitly flattened documents remain flat
+    let doc = flatten(items.render());
+    assert_str_eq!(format!("{:#}", doc), "[1, 2, 3]");
+}
+
+#[test]
+fn min_widths_match_brute_force_rendering() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let docs = [
+        ast.render(),
+        vec![1u32, 20, 300].render(),
+        const_text("fn") + indent(4, nl() + "body"),
+        Document::Empty,
+    ];
+    for doc in docs.iter() {
+        let outputs = (0..=100)
+            .map(|width| render_with_config(doc, &PrintConfig::new(width)))
+            .collect::<Vec<_>>();
+        let line_count =
+            |output: &String| if output.is_empty() { 0 } else { output.lines().count() };
+
+        let expected = outputs
+            .iter()
+            .position(|output| !output.contains('\n'))
+            .map(|width| width as u32);
+        assert_eq!(doc.min_flat_width(), expected, "for {doc:?}");
+
+        for max_lines in 0..10 {
+            let expected = outputs
+                .iter()
+                .position(|output| line_count(output) <= max_lines)
+                .map(|width| width as u32);
+            assert_eq!(
+                doc.min_width_for_lines(max_lines),
+                expected,
+                "for {doc:?} in {max_lines} lines"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let id = GroupId::new(1);
+    let doc = group_with_id(id, ast.render())
+        + if_group_breaks(id, nl(), const_text(" "))
+        + prefix_lines("// ", split("first\n\nsecond"))
+        + with_span(7, ansi_text("\x1b[31mred\x1b[0m"))
+        + truncate(4, text("日本語テキスト"))
+        + tabstop(20)
+        + flatten(vec![1u32, 2].render())
+        + '\t';
+
+    let json = serde_json::to_string(&doc).unwrap();
+    let deserialized = serde_json::from_str::<Document>(&json).unwrap();
+    assert_eq!(deserialized, doc);
+    assert_str_eq!(format!("{deserialized:40}"), format!("{doc:40}"));
+
+    // Escape sequences in plain text are counted towards its width, unlike in `ansi_text`
+    let doc = text("\x1b[1m") + ansi_text("\x1b[0m");
+    let json = serde_json::to_string(&doc).unwrap();
+    assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), doc);
+}
+
+#[test]
+fn reindent_shifts_indentation() {
+    let doc = const_text("fn") + indent(4, nl() + "a" + indent(2, nl() + "b")) + nl() + "end";
+    let config = PrintConfig::new(80);
+    assert_str_eq!(render_with_config(&doc, &config), "fn\n    a\n      b\nend");
+    // Every level of indentation is shifted, so nested indentation is shifted cumulatively
+    assert_str_eq!(
+        render_with_config(&doc.clone().reindent(2), &config),
+        "fn\n      a\n          b\nend"
+    );
+    // Indentation is clamped at zero
+    assert_str_eq!(render_with_config(&doc.clone().reindent(-3), &config), "fn\n a\n b\nend");
+    assert_eq!(doc.clone().reindent(0), doc);
+}
+
+#[test]
+fn rebuilding_preserves_shared_subdocuments() {
+    // Each level of nesting shares its elements between the flat and broken layouts, so the
+    // document is exponentially larger than its number of distinct nodes
+    let mut doc = text("leaf");
+    for _ in 0..64 {
+        doc = alloc::vec![doc.clone(), const_text("x")].render();
+    }
+    let config = PrintConfig::new(40);
+    let expected = render_with_config(&doc, &config);
+    assert_str_eq!(render_with_config(&doc.clone().into_owned(), &config), expected);
+    assert_str_eq!(render_with_config(&doc.clone().reindent(0), &config), expected);
+    let reindented = render_with_config(&doc.clone().reindent(4), &config);
+    assert!(reindented.lines().nth(1).unwrap().starts_with("        ["), "{reindented}");
+}
+
+#[test]
+fn into_owned_converts_borrowed_text() {
+    fn all_owned(doc: &Document) -> bool {
+        match doc {
+            Document::Text(text, _) | Document::Prefix(text, ..)
+                if matches!(text, Cow::Borrowed(_)) =>
+            {
+                false
+            },
+            Document::Empty
+            | Document::Newline
+            | Document::Char(..)
+            | Document::Text(..)
+            | Document::SharedText(..)
+            | Document::TabStop(_) => true,
+            Document::Flatten(x)
+            | Document::Indent(_, x)
+            | Document::Group(_, x)
+            | Document::ExpandGroup(_, x)
+            | Document::Prefix(_, _, x)
+            | Document::Annotated(_, x)
+            | Document::Truncate(_, x) => all_owned(x),
+            Document::Concat(x, y) | Document::Choice(x, y) | Document::IfBreak(_, x, y) => {
+                all_owned(x) && all_owned(y)
+            },
+        }
+    }
+
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let doc = prefix_lines("// ", ast.render()) + nl() + text(String::from("owned"));
+    assert!(!all_owned(&doc));
+
+    let owned = doc.clone().into_owned();
+    assert!(all_owned(&owned));
+    assert_eq!(owned, doc);
+    for width in [20, 80] {
+        let config = PrintConfig::new(width);
+        assert_str_eq!(render_with_config(&owned, &config), render_with_config(&doc, &config));
+    }
+}
+
+#[test]
+fn line_count_agrees_with_rendering_at_all_widths() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let map = [(1u32, "one"), (2, "two"), (3, "three")]
+        .into_iter()
+        .collect::<alloc::collections::BTreeMap<_, _>>();
+    let docs = [
+        ast.render(),
+        prefix_lines("// ", ast.render()),
+        vec![vec![1u32, 2], vec![3, 4, 5]].render(),
+        map.render(),
+        split("first\n\nlast\n"),
+        truncate(10, ast.render()) + nl(),
+        Document::Empty,
+    ];
+    for doc in docs.iter() {
+        for width in 0..=100 {
+            let rendered = render_with_config(doc, &PrintConfig::new(width));
+            // The final line is counted even if it is empty, i.e. follows a trailing line break
+            let expected = if rendered.is_empty() {
+                0
+            } else {
+                rendered.matches('\n').count() + 1
+            };
+            assert_eq!(doc.line_count_at(width), expected, "at width {width} for {rendered:?}");
+        }
+    }
+}
+
+#[test]
+fn render_preview_is_prefix_of_full_render() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let doc = ast.render();
+    for width in [20, 40, 80] {
+        let full = format!("{doc:width$}");
+        let total = full.lines().count();
+        for max_lines in 0..=total + 1 {
+            let (preview, elided) = doc.render_preview(width, max_lines);
+            let expected = full.lines().take(max_lines).collect::<Vec<_>>().join("\n");
+            assert_str_eq!(preview, expected);
+            assert_eq!(elided, max_lines < total, "at width {width} with {max_lines} lines");
+        }
+    }
+
+    // Printing stops at the cutoff: this document shares its subtrees, so that it occupies
+    // little memory, but has 2^40 lines, which could never be rendered in full
+    let mut huge = group(const_text("line") + (const_text(" ") | nl()) + "x");
+    for _ in 0..40 {
+        huge = huge.clone() + nl() + huge;
+    }
+    let (preview, elided) = huge.render_preview(80, 3);
+    assert_str_eq!(preview, "line x\nline x\nline x");
+    assert!(elided);
+
+    // A blank final line is kept, but the line break which would follow it is not
+    let doc = const_text("a") + nl() + nl() + "b";
+    assert_eq!(doc.render_preview(80, 2), (String::from("a\n"), true));
+    assert_eq!(doc.render_preview(80, 3), (String::from("a\n\nb"), false));
+}
+
+#[test]
+fn group_or_break_breaks_all_choices() {
+    let sep = || const_text(", ") | (const_text(",") + nl());
+    let items = const_text("aaaa") + sep() + "bbbb" + sep() + "cccccccccc";
+
+    // A group makes each choice independently once broken...
+    let doc = group(items.clone());
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "aaaa, bbbb, cccccccccc");
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(12)), "aaaa, bbbb,\ncccccccccc");
+
+    // ...whereas this breaks all of them at once
+    let doc = group_or_break(items.clone(), false);
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "aaaa, bbbb, cccccccccc");
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(12)), "aaaa,\nbbbb,\ncccccccccc");
+
+    // Forcing the group to break does so even when it would fit, and breaks enclosing groups
+    let doc = group_or_break(items.clone(), true);
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "aaaa,\nbbbb,\ncccccccccc");
+    let doc = group_or_break(
+        const_text("[") + (const_text(" ") | nl()) + group_or_break(items, true),
+        false,
+    );
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "[\naaaa,\nbbbb,\ncccccccccc");
+
+    // Nested groups are still laid out independently
+    let doc = group_or_break(const_text("x") + sep() + group(const_text("y") + sep() + "z"), true);
+    assert_str_eq!(render_with_config(&doc, &PrintConfig::new(80)), "x,\ny, z");
+}
+
+#[test]
+fn render_checked_reports_overflowing_lines() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let doc = ast.render();
+    let config = PrintConfig::new(40);
+    assert_eq!(render_checked(&doc, &config), Ok(format!("{doc:40}")));
+
+    // Tokens can never be broken, so they overflow a narrow page
+    let doc =
+        const_text("short") + nl() + "a_very_long_token" + nl() + "ok" + nl() + "another_token";
+    let report = render_checked(&doc, &PrintConfig::new(10)).unwrap_err();
+    assert_str_eq!(report.output, format!("{doc:10}"));
+    assert_eq!(
+        report.overflows,
+        [Overflow { line: 1, width: 17 }, Overflow { line: 3, width: 13 }]
+    );
+    assert_str_eq!(
+        report.to_string(),
+        "output exceeds the page width of 10 columns on line 2 (17 columns), line 4 (13 columns)"
+    );
+
+    // Tabs and the initial indentation are accounted for like when choosing a layout
+    let doc = const_text("\tx");
+    assert!(render_checked(&doc, &PrintConfig::new(9)).is_ok());
+    assert!(render_checked(&doc, &PrintConfig::new(9).with_initial_indent(1)).is_ok());
+    assert!(render_checked(&doc, &PrintConfig::new(9).with_tab_width(9)).is_err());
+}
+
+#[test]
+fn trailing_width_measures_last_line() {
+    assert_eq!(Document::Empty.trailing_width(), 0);
+    assert_eq!(const_text("let x =").trailing_width(), 7);
+    // Choices are measured using their single-line layout
+    assert_eq!(vec![1u32, 2, 3].render().trailing_width(), 9);
+    // Only the content following the last line break is measured, including its indentation
+    let doc = const_text("fn foo() {") + indent(4, nl() + "body") + nl() + "}";
+    assert_eq!(doc.trailing_width(), 1);
+    let doc = const_text("fn foo() {") + indent(4, nl() + "let x = " + text("日本"));
+    assert_eq!(doc.trailing_width(), 16);
+    // A document ending with a line break has nothing on its last line
+    assert_eq!((const_text("line") + nl()).trailing_width(), 0);
+    assert_eq!(indent(4, const_text("line") + nl()).trailing_width(), 0);
+}
+
+#[test]
+fn pathologically_small_widths() {
+    let ast = fun!(square_plus_1 (a : number, b : number) => number in let_expr!(c = mul!(a, b) => add!(c, 1)));
+    let docs = [
+        ast.render(),
+        vec![vec![1u32, 2], vec![3, 4, 5]].render(),
+        prefix_lines("// ", ast.render()),
+    ];
+    for doc in docs.iter() {
+        // Nothing fits on a page of zero width, so every choice uses its multi-line layout
+        let expanded = render_with_config(doc, &PrintConfig::new(80).with_expand_all(true));
+        assert_str_eq!(render_with_config(doc, &PrintConfig::new(0)), expanded);
+
+        for width in 0..=2 {
+            // Splitting text always makes progress, even when the indentation exceeds the width,
+            // and never produces blank lines
+            let config = PrintConfig::new(width).with_split_long_text(true);
+            let output = render_with_config(doc, &config);
+            assert!(
+                output.lines().all(|line| !line.trim_start_matches("//").trim().is_empty()),
+                "{output}"
+            );
+        }
+    }
+
+    let config = PrintConfig::new(0).with_split_long_text(true);
+    let doc = indent(2, nl() + const_text("abc def"));
+    assert_str_eq!(render_with_config(&doc, &config), "\n  a\n  b\n  c\n  d\n  e\n  f");
+    let config = PrintConfig::new(2).with_split_long_text(true);
+    let doc = indent(8, nl() + const_text("ab"));
+    assert_str_eq!(render_with_config(&doc, &config), "\n        a\n        b");
+}
@@ -0,0 +1,129 @@
+//! Measurement of the display width of text.
+//!
+//! By default, widths are computed using [unicode_width]. When the `grapheme` feature is enabled,
+//! text is instead measured one extended grapheme cluster at a time, so that sequences such as
+//! emoji with modifiers, or characters followed by combining marks, are measured as the single
+//! glyph they are displayed as.
+
+/// Returns the display width of `c`
+#[inline]
+pub fn char_width(c: char) -> usize {
+    unicode_width::UnicodeWidthChar::width(c).unwrap_or(0)
+}
+
+/// Returns the display width of `s`
+#[cfg(not(feature = "grapheme"))]
+#[inline]
+pub fn str_width(s: &str) -> usize {
+    unicode_width::UnicodeWidthStr::width(s)
+}
+
+/// Returns the display width of `s`
+#[cfg(feature = "grapheme")]
+pub fn str_width(s: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    s.graphemes(true).map(cluster_width).sum()
+}
+
+/// Returns the display width of `s`, ignoring any ANSI escape sequences it contains
+pub fn ansi_str_width(s: &str) -> usize {
+    runs(s).filter(|(_, _, escape)| !escape).map(|(_, run, _)| str_width(run)).sum()
+}
+
+/// Splits `s` into the smallest units of text which can be measured independently, and which
+/// may be placed on separate lines, returning the byte offset, the unit, and its display width.
+///
+/// ANSI escape sequences are treated as a single unit of zero width.
+pub fn segments(s: &str) -> impl Iterator<Item = (usize, &str, usize)> {
+    runs(s).flat_map(|(offset, run, escape)| {
+        let (sequence, text) = if escape {
+            (Some((offset, run, 0)), "")
+        } else {
+            (None, run)
+        };
+        sequence
+            .into_iter()
+            .chain(units(text).map(move |(start, unit, width)| (offset + start, unit, width)))
+    })
+}
+
+/// Splits `s` into runs of either plain text, or a single ANSI escape sequence, returning the
+/// byte offset of the run, the run itself, and whether or not it is an escape sequence.
+fn runs(s: &str) -> impl Iterator<Item = (usize, &str, bool)> {
+    let mut offset = 0;
+    core::iter::from_fn(move || {
+        let rest = &s[offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        let start = offset;
+        if rest.starts_with('\x1b') {
+            offset += escape_len(rest);
+            Some((start, &s[start..offset], true))
+        } else {
+            offset += rest.find('\x1b').unwrap_or(rest.len());
+            Some((start, &s[start..offset], false))
+        }
+    })
+}
+
+/// Returns the length in bytes of the ANSI escape sequence at the start of `s`.
+///
+/// Truncated or malformed sequences extend as far as they can, i.e. to the end of `s` at most.
+fn escape_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    debug_assert_eq!(bytes.first(), Some(&0x1b));
+    match bytes.get(1) {
+        // Control Sequence Introducer, terminated by a byte in the range 0x40..=0x7e
+        Some(b'[') => bytes[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map(|pos| pos + 3)
+            .unwrap_or(bytes.len()),
+        // Operating System Command, terminated by BEL or ST (i.e. ESC \)
+        Some(b']') => {
+            let mut len = 2;
+            while len < bytes.len() {
+                match bytes[len] {
+                    0x07 => return len + 1,
+                    0x1b if bytes.get(len + 1) == Some(&b'\\') => return len + 2,
+                    _ => len += 1,
+                }
+            }
+            len
+        },
+        // Other two-byte escape sequences
+        Some(0x40..=0x5f) => 2,
+        // A lone escape character
+        _ => 1,
+    }
+}
+
+/// Splits `s` into the smallest units of text which can be measured independently, and which
+/// may be placed on separate lines, i.e. characters, returning the byte offset, the unit, and
+/// its display width.
+#[cfg(not(feature = "grapheme"))]
+fn units(s: &str) -> impl Iterator<Item = (usize, &str, usize)> {
+    s.char_indices()
+        .map(|(offset, c)| (offset, &s[offset..(offset + c.len_utf8())], char_width(c)))
+}
+
+/// Splits `s` into the smallest units of text which can be measured independently, and which
+/// may be placed on separate lines, i.e. extended grapheme clusters, returning the byte offset,
+/// the unit, and its display width.
+#[cfg(feature = "grapheme")]
+fn units(s: &str) -> impl Iterator<Item = (usize, &str, usize)> {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    s.grapheme_indices(true)
+        .map(|(offset, cluster)| (offset, cluster, cluster_width(cluster)))
+}
+
+/// Returns the display width of a single extended grapheme cluster.
+///
+/// A grapheme cluster is displayed as a single glyph, which is at most two columns wide.
+#[cfg(feature = "grapheme")]
+fn cluster_width(cluster: &str) -> usize {
+    core::cmp::min(unicode_width::UnicodeWidthStr::width(cluster), 2)
+}